@@ -1,27 +1,21 @@
 use std::collections::{HashMap, HashSet};
 
 // Importing the Point struct from the geometry module
+use crate::topology::{Square, Topology, Wrapped};
 use crate::Point;
 
-// Binary operation type alias
-type BinaryOperation<P> = fn(&P, &P) -> P;
-
 // Structure representing the Board
 #[derive(Clone)]
-pub struct Board<P> {
-    filled: HashMap<P, String>,
-    unfilled: HashSet<P>,
-    pub all: Vec<P>,
-    adder: BinaryOperation<P>,
-    dirs: Vec<P>,
+pub struct Board<T: Topology> {
+    filled: HashMap<T::Point, String>,
+    unfilled: HashSet<T::Point>,
+    pub all: Vec<T::Point>,
+    topology: T,
 }
 
-impl<P> Board<P>
-where
-    P: Copy + Eq + std::hash::Hash,
-{
+impl<T: Topology> Board<T> {
     // Constructor
-    pub fn new(ps: Vec<P>, adder: BinaryOperation<P>, dirs: Vec<P>) -> Self {
+    pub fn new(ps: Vec<T::Point>, topology: T) -> Self {
         let mut unfilled = HashSet::new();
         for p in &ps {
             unfilled.insert(*p);
@@ -31,18 +25,21 @@ where
             filled: HashMap::new(),
             unfilled,
             all: ps,
-            adder,
-            dirs,
+            topology,
         }
     }
 
+    pub fn topology(&self) -> &T {
+        &self.topology
+    }
+
     // Private method to recursively spread and collect reachable points
-    fn spread(&self, p: &P, limit: usize, accum: &mut HashSet<P>) {
+    fn spread(&self, p: &T::Point, limit: usize, accum: &mut HashSet<T::Point>) {
         if accum.len() < limit && self.unfilled.contains(p) && !accum.contains(p) {
             accum.insert(*p);
 
-            for d in &self.dirs {
-                self.spread(&(self.adder)(p, d), limit, accum);
+            for n in self.topology.neighbours(p) {
+                self.spread(&n, limit, accum);
 
                 if accum.len() == limit {
                     break;
@@ -52,19 +49,25 @@ where
     }
 
     // Public method to find the number of reachable points within a limit
-    pub fn reachable(&self, p: &P, limit: usize) -> usize {
+    pub fn reachable(&self, p: &T::Point, limit: usize) -> usize {
         let mut reached = HashSet::new();
         self.spread(p, limit, &mut reached);
         reached.len()
     }
 
     // Method to fill the board with markers at specified points
-    pub fn fill(&mut self, ps: &Vec<P>, offset: P, marker: &str) -> Option<Vec<P>> {
+    pub fn fill(
+        &mut self,
+        ps: &Vec<T::Point>,
+        offset: T::Point,
+        marker: &str,
+    ) -> Option<Vec<T::Point>> {
         let mut eps = vec![];
 
         for p in ps {
-            let op = (self.adder)(p, &offset);
-            if !self.unfilled.contains(&op) {
+            let op = self.topology.add(p, &offset);
+            // on a wrapped board a long piece can run into itself
+            if !self.unfilled.contains(&op) || eps.contains(&op) {
                 return None;
             } else {
                 eps.push(op);
@@ -78,7 +81,21 @@ where
         Some(eps)
     }
 
-    pub fn unfill(&mut self, eps: Vec<P>) {
+    // Fills cells already translated onto the board, e.g. a placement table
+    // entry, if all of them are free
+    pub fn fill_points(&mut self, ps: &[T::Point], marker: &str) -> Option<Vec<T::Point>> {
+        if !ps.iter().all(|p| self.unfilled.contains(p)) {
+            return None;
+        }
+        for p in ps {
+            self.unfilled.remove(p);
+            self.filled.insert(*p, marker.to_string());
+        }
+
+        Some(ps.to_vec())
+    }
+
+    pub fn unfill(&mut self, eps: Vec<T::Point>) {
         for ep in eps {
             self.unfilled.insert(ep);
             self.filled.remove(&ep);
@@ -86,7 +103,7 @@ where
     }
 
     // Method to get the marker at a specified point
-    pub fn at(&self, p: &P) -> Option<&str> {
+    pub fn at(&self, p: &T::Point) -> Option<&str> {
         if self.unfilled.contains(p) {
             return None; // fillable square
         }
@@ -95,29 +112,24 @@ where
     }
 
     // Method to get the remaining unfilled points
-    pub fn remaining(&self) -> Vec<&P> {
+    pub fn remaining(&self) -> Vec<&T::Point> {
         self.unfilled.iter().collect()
     }
 }
 
-// Function to add two Points
-pub fn add(a: &Point, b: &Point) -> Point {
-    Point {
-        x: a.x + b.x,
-        y: a.y + b.y,
-    }
+// Function to create a Point Board
+pub fn make_point_board(points: Vec<Point>) -> Board<Square> {
+    Board::new(points, Square)
 }
 
-// Function to create a Point Board
-pub fn make_point_board(points: Vec<Point>) -> Board<Point> {
-    Board::new(
-        points,
-        add,
-        vec![
-            Point { x: 0, y: -1 },
-            Point { x: 0, y: 1 },
-            Point { x: -1, y: 0 },
-            Point { x: 1, y: 0 },
-        ],
-    )
+// Board whose columns and/or rows wrap around, sized by the given points
+pub fn make_wrapped_board(points: Vec<Point>, wrap_x: bool, wrap_y: bool) -> Board<Wrapped> {
+    let width = points.iter().map(|p| p.x + 1).max().unwrap_or(0);
+    let height = points.iter().map(|p| p.y + 1).max().unwrap_or(0);
+
+    let topology = Wrapped {
+        width: Some(width).filter(|_| wrap_x),
+        height: Some(height).filter(|_| wrap_y),
+    };
+    Board::new(points, topology)
 }
@@ -0,0 +1,455 @@
+use crate::sat::Cnf;
+
+// Literals are numbered 2 * var for the variable and 2 * var + 1 for its
+// negation, with variables counted from 0
+type Lit = usize;
+
+fn lit(dimacs: i32) -> Lit {
+    let var = dimacs.unsigned_abs() as usize - 1;
+    2 * var + (dimacs < 0) as usize
+}
+
+fn var(l: Lit) -> usize {
+    l / 2
+}
+
+fn negate(l: Lit) -> Lit {
+    l ^ 1
+}
+
+// Conflict driven clause learning: two watched literals per clause, first
+// UIP learning with backjumping, activity based branching and restarts
+pub struct Cdcl {
+    clauses: Vec<Vec<Lit>>,
+    // clauses watching each literal, looked at when it becomes false
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    // where each decision level starts on the trail
+    trail_lim: Vec<usize>,
+    head: usize,
+    activity: Vec<f64>,
+    bump: f64,
+    phase: Vec<bool>,
+    unsat: bool,
+    // learnt clauses, which may be thrown away again
+    learnts: Vec<usize>,
+    max_learnts: usize,
+    pub conflicts: usize,
+    pub decisions: usize,
+}
+
+pub fn create_cdcl(cnf: &Cnf) -> Cdcl {
+    let mut cdcl = Cdcl {
+        clauses: vec![],
+        watches: vec![vec![]; 2 * cnf.vars],
+        values: vec![None; cnf.vars],
+        levels: vec![0; cnf.vars],
+        reasons: vec![None; cnf.vars],
+        trail: vec![],
+        trail_lim: vec![],
+        head: 0,
+        activity: vec![0.0; cnf.vars],
+        bump: 1.0,
+        // placements are mostly unused, so guess false first
+        phase: vec![false; cnf.vars],
+        unsat: false,
+        learnts: vec![],
+        max_learnts: 1000,
+        conflicts: 0,
+        decisions: 0,
+    };
+
+    for clause in &cnf.clauses {
+        let lits: Vec<Lit> = clause.iter().map(|&l| lit(l)).collect();
+        add_clause(&mut cdcl, lits);
+    }
+
+    cdcl
+}
+
+fn value(cdcl: &Cdcl, l: Lit) -> Option<bool> {
+    cdcl.values[var(l)].map(|v| v != (l & 1 == 1))
+}
+
+fn level(cdcl: &Cdcl) -> usize {
+    cdcl.trail_lim.len()
+}
+
+fn assign(cdcl: &mut Cdcl, l: Lit, reason: Option<usize>) {
+    let v = var(l);
+    cdcl.values[v] = Some(l & 1 == 0);
+    cdcl.levels[v] = level(cdcl);
+    cdcl.reasons[v] = reason;
+    cdcl.trail.push(l);
+}
+
+fn watch(cdcl: &mut Cdcl, lits: Vec<Lit>) -> usize {
+    let index = cdcl.clauses.len();
+    cdcl.watches[lits[0]].push(index);
+    cdcl.watches[lits[1]].push(index);
+    cdcl.clauses.push(lits);
+    index
+}
+
+// Adds a clause while at level 0, dropping literals already false there
+fn add_clause(cdcl: &mut Cdcl, mut lits: Vec<Lit>) {
+    lits.sort();
+    lits.dedup();
+    if lits.iter().any(|&l| value(cdcl, l) == Some(true)) {
+        return;
+    }
+    lits.retain(|&l| value(cdcl, l).is_none());
+
+    match lits.len() {
+        0 => cdcl.unsat = true,
+        1 => assign(cdcl, lits[0], None),
+        _ => {
+            watch(cdcl, lits);
+        }
+    }
+}
+
+// Runs unit propagation, returning a clause that became false if any
+fn propagate(cdcl: &mut Cdcl) -> Option<usize> {
+    while cdcl.head < cdcl.trail.len() {
+        let falsified = negate(cdcl.trail[cdcl.head]);
+        cdcl.head += 1;
+
+        let watching = std::mem::take(&mut cdcl.watches[falsified]);
+        let mut kept = Vec::with_capacity(watching.len());
+        let mut conflict = None;
+
+        for (i, &c) in watching.iter().enumerate() {
+            if conflict.is_some() {
+                kept.extend_from_slice(&watching[i..]);
+                break;
+            }
+
+            // clauses thrown away by reduce are dropped from the lists here
+            if cdcl.clauses[c].is_empty() {
+                continue;
+            }
+
+            // keep the false literal in the second slot
+            if cdcl.clauses[c][0] == falsified {
+                cdcl.clauses[c].swap(0, 1);
+            }
+            let first = cdcl.clauses[c][0];
+            if value(cdcl, first) == Some(true) {
+                kept.push(c);
+                continue;
+            }
+
+            let replacement = (2..cdcl.clauses[c].len())
+                .find(|&k| value(cdcl, cdcl.clauses[c][k]) != Some(false));
+            match replacement {
+                Some(k) => {
+                    cdcl.clauses[c].swap(1, k);
+                    let l = cdcl.clauses[c][1];
+                    cdcl.watches[l].push(c);
+                }
+                None => {
+                    kept.push(c);
+                    match value(cdcl, first) {
+                        Some(false) => conflict = Some(c),
+                        _ => assign(cdcl, first, Some(c)),
+                    }
+                }
+            }
+        }
+
+        cdcl.watches[falsified] = kept;
+        if conflict.is_some() {
+            return conflict;
+        }
+    }
+
+    None
+}
+
+fn bump(cdcl: &mut Cdcl, v: usize) {
+    cdcl.activity[v] += cdcl.bump;
+    if cdcl.activity[v] > 1e100 {
+        for a in cdcl.activity.iter_mut() {
+            *a *= 1e-100;
+        }
+        cdcl.bump *= 1e-100;
+    }
+}
+
+// Learns a clause from the conflict that has exactly one literal of the
+// current level, returning it with that literal first and the level to jump
+// back to
+fn analyze(cdcl: &mut Cdcl, conflict: usize) -> (Vec<Lit>, usize) {
+    let mut seen = vec![false; cdcl.values.len()];
+    let mut learnt = vec![0];
+    let mut pending = 0;
+    let mut clause = conflict;
+    let mut index = cdcl.trail.len();
+    let mut uip;
+
+    loop {
+        let lits = cdcl.clauses[clause].clone();
+        // the first literal of a reason clause is the one it implied
+        let skip = if clause == conflict { 0 } else { 1 };
+        for &l in &lits[skip..] {
+            let v = var(l);
+            if !seen[v] && cdcl.levels[v] > 0 {
+                seen[v] = true;
+                bump(cdcl, v);
+                if cdcl.levels[v] == level(cdcl) {
+                    pending += 1;
+                } else {
+                    learnt.push(l);
+                }
+            }
+        }
+
+        // walk back to the next literal of this level taking part
+        loop {
+            index -= 1;
+            uip = cdcl.trail[index];
+            if seen[var(uip)] {
+                break;
+            }
+        }
+        pending -= 1;
+        if pending == 0 {
+            break;
+        }
+        clause = cdcl.reasons[var(uip)].unwrap();
+    }
+    learnt[0] = negate(uip);
+
+    // second highest level goes in the second slot so it is watched
+    let mut back = 0;
+    if learnt.len() > 1 {
+        let (i, _) = learnt
+            .iter()
+            .enumerate()
+            .skip(1)
+            .max_by_key(|(_, &l)| cdcl.levels[var(l)])
+            .unwrap();
+        learnt.swap(1, i);
+        back = cdcl.levels[var(learnt[1])];
+    }
+
+    (learnt, back)
+}
+
+fn backtrack(cdcl: &mut Cdcl, to: usize) {
+    if level(cdcl) <= to {
+        return;
+    }
+
+    let start = cdcl.trail_lim[to];
+    for &l in &cdcl.trail[start..] {
+        let v = var(l);
+        cdcl.phase[v] = l & 1 == 0;
+        cdcl.values[v] = None;
+        cdcl.reasons[v] = None;
+    }
+    cdcl.trail.truncate(start);
+    cdcl.trail_lim.truncate(to);
+    cdcl.head = start;
+}
+
+// Throws away the longer half of the learnt clauses once there are too many,
+// keeping those that are the reason for a current assignment
+fn reduce(cdcl: &mut Cdcl) {
+    if cdcl.learnts.len() < cdcl.max_learnts {
+        return;
+    }
+
+    let mut learnts = std::mem::take(&mut cdcl.learnts);
+    learnts.sort_by_key(|&c| cdcl.clauses[c].len());
+    for &c in &learnts[learnts.len() / 2..] {
+        let locked = cdcl.reasons[var(cdcl.clauses[c][0])] == Some(c);
+        if cdcl.clauses[c].len() > 2 && !locked {
+            cdcl.clauses[c] = vec![];
+        }
+    }
+    learnts.retain(|&c| !cdcl.clauses[c].is_empty());
+
+    cdcl.learnts = learnts;
+    cdcl.max_learnts += 100;
+}
+
+fn pick(cdcl: &Cdcl) -> Option<usize> {
+    (0..cdcl.values.len())
+        .filter(|&v| cdcl.values[v].is_none())
+        .max_by(|&a, &b| cdcl.activity[a].total_cmp(&cdcl.activity[b]))
+}
+
+// Finds a model and returns its true variables, numbered from 1 as in
+// DIMACS, or None when there is none left
+pub fn solve(cdcl: &mut Cdcl) -> Option<Vec<usize>> {
+    let mut restart_at = 100;
+    let mut since_restart = 0;
+
+    while !cdcl.unsat {
+        if let Some(conflict) = propagate(cdcl) {
+            cdcl.conflicts += 1;
+            if level(cdcl) == 0 {
+                cdcl.unsat = true;
+                break;
+            }
+
+            let (learnt, back) = analyze(cdcl, conflict);
+            backtrack(cdcl, back);
+            if learnt.len() == 1 {
+                assign(cdcl, learnt[0], None);
+            } else {
+                let first = learnt[0];
+                let c = watch(cdcl, learnt);
+                cdcl.learnts.push(c);
+                assign(cdcl, first, Some(c));
+            }
+            reduce(cdcl);
+            cdcl.bump /= 0.95;
+
+            since_restart += 1;
+            if since_restart >= restart_at {
+                backtrack(cdcl, 0);
+                since_restart = 0;
+                restart_at += restart_at / 2;
+            }
+        } else {
+            match pick(cdcl) {
+                Some(v) => {
+                    cdcl.decisions += 1;
+                    cdcl.trail_lim.push(cdcl.trail.len());
+                    let l = 2 * v + (!cdcl.phase[v]) as usize;
+                    assign(cdcl, l, None);
+                }
+                None => {
+                    let trues = (0..cdcl.values.len())
+                        .filter(|&v| cdcl.values[v] == Some(true))
+                        .map(|v| v + 1)
+                        .collect();
+                    return Some(trues);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Rules out a model found by `solve` so the next call finds another one.
+// Every piece takes exactly one placement, so forbidding the chosen ones
+// together is enough
+pub fn block(cdcl: &mut Cdcl, trues: &[usize]) {
+    backtrack(cdcl, 0);
+    let lits = trues.iter().map(|&v| lit(-(v as i32))).collect();
+    add_clause(cdcl, lits);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memo::count_solutions;
+    use crate::puzzle::parse_puzzle;
+    use crate::sat::to_cnf;
+
+    fn cnf(vars: usize, clauses: &[&[i32]]) -> Cnf {
+        Cnf {
+            vars,
+            clauses: clauses.iter().map(|clause| clause.to_vec()).collect(),
+        }
+    }
+
+    fn satisfies(cnf: &Cnf, trues: &[usize]) -> bool {
+        cnf.clauses.iter().all(|clause| {
+            clause
+                .iter()
+                .any(|&l| trues.contains(&(l.unsigned_abs() as usize)) == (l > 0))
+        })
+    }
+
+    // Every model, found by blocking each one in turn. This lists them all
+    // only if no model's true variables contain another's, as `block` assumes
+    fn all_models(cnf: &Cnf) -> (Vec<Vec<usize>>, Cdcl) {
+        let mut engine = create_cdcl(cnf);
+        let mut models = vec![];
+        while let Some(trues) = solve(&mut engine) {
+            block(&mut engine, &trues);
+            models.push(trues);
+        }
+        (models, engine)
+    }
+
+    #[test]
+    fn finds_a_model_of_a_satisfiable_formula() {
+        // 1 or 2 forces 3, which rules out 4 and so forces 1
+        let formula = cnf(4, &[&[1, 2], &[-1, 3], &[-2, 3], &[-3, -4], &[4, 1], &[-2]]);
+        let trues = solve(&mut create_cdcl(&formula)).unwrap();
+        assert!(satisfies(&formula, &trues));
+        assert_eq!(trues, [1, 3]);
+    }
+
+    #[test]
+    fn finds_no_model_of_an_unsatisfiable_formula() {
+        assert_eq!(solve(&mut create_cdcl(&cnf(1, &[&[1], &[-1]]))), None);
+        assert_eq!(
+            solve(&mut create_cdcl(&cnf(2, &[&[1, 2], &[-1], &[-2]]))),
+            None
+        );
+
+        // three pigeons in two holes, which takes learning and backjumping
+        let hole = |pigeon: i32, h: i32| 2 * pigeon + h + 1;
+        let mut clauses = vec![];
+        for pigeon in 0..3 {
+            clauses.push(vec![hole(pigeon, 0), hole(pigeon, 1)]);
+        }
+        for h in 0..2 {
+            for a in 0..3 {
+                for b in a + 1..3 {
+                    clauses.push(vec![-hole(a, h), -hole(b, h)]);
+                }
+            }
+        }
+        let mut engine = create_cdcl(&Cnf { vars: 6, clauses });
+        assert_eq!(solve(&mut engine), None);
+        assert!(engine.conflicts > 0);
+    }
+
+    #[test]
+    fn blocking_lists_every_model_once() {
+        // exactly one of four variables
+        let mut clauses: Vec<Vec<i32>> = vec![vec![1, 2, 3, 4]];
+        for a in 1..=4 {
+            for b in a + 1..=4 {
+                clauses.push(vec![-a, -b]);
+            }
+        }
+        let (mut models, _) = all_models(&Cnf { vars: 4, clauses });
+        models.sort();
+        assert_eq!(models, [[1], [2], [3], [4]]);
+    }
+
+    #[test]
+    fn counts_the_solutions_of_a_puzzle() {
+        // a 6x5 rectangle with six pentominoes, enough conflicts to restart
+        // and throw learnt clauses away
+        let text = "board 2\n".to_string()
+            + &"o o o o o o\n".repeat(5)
+            + "piece F\n **\n**\n *\npiece I\n*****\npiece L\n****\n*\n\
+               piece P\n***\n**\npiece U\n* *\n***\npiece Y\n****\n *\n";
+        let puzzle = parse_puzzle(&text).unwrap();
+        let board = puzzle.make_board(&[]).unwrap();
+        let table = puzzle.placement_table();
+        let (formula, _) = to_cnf(&table, &board);
+
+        let (models, engine) = all_models(&formula);
+        for trues in &models {
+            assert!(satisfies(&formula, trues));
+        }
+        assert!(engine.conflicts > 1000);
+        let (expected, _) = count_solutions(&table, &board, &puzzle.shapes, None).unwrap();
+        assert_eq!(models.len() as u64, expected);
+    }
+}
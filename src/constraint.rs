@@ -0,0 +1,288 @@
+use std::fmt::Write;
+
+use crate::board::Board;
+use crate::geometry::Point;
+use crate::sat::parse_model;
+use crate::solver::Placement;
+use crate::stringify::format_placement;
+use crate::table::PlacementTable;
+use crate::topology::Square;
+
+fn format_points(ps: &[Point]) -> String {
+    let ps: Vec<String> = ps.iter().map(|p| format!("({},{})", p.x, p.y)).collect();
+    ps.join(" ")
+}
+
+// The puzzle spelled out as comment lines: open and blocked cells, every
+// orientation of every piece and what each placement variable stands for
+fn describe(
+    comment: &str,
+    table: &PlacementTable<Square>,
+    board: &Board<Square>,
+    placements: &[Placement<Point>],
+) -> String {
+    let open: Vec<Point> = board.remaining().into_iter().cloned().collect();
+    let blocked: Vec<Point> = board
+        .all
+        .iter()
+        .filter(|p| board.at(p).is_some())
+        .cloned()
+        .collect();
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} open cells: {}",
+        comment,
+        format_points(&sort(open))
+    );
+    let _ = writeln!(
+        out,
+        "{} blocked cells: {}",
+        comment,
+        format_points(&blocked)
+    );
+    for (label, vs) in table.labels.iter().zip(&table.variants) {
+        for (i, v) in vs.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{} piece {} orientation {}: {}",
+                comment,
+                label,
+                i,
+                format_points(v)
+            );
+        }
+    }
+    for (i, pl) in placements.iter().enumerate() {
+        let _ = writeln!(out, "{} p{} = {}", comment, i + 1, format_placement(pl));
+    }
+
+    out
+}
+
+fn placements(table: &PlacementTable<Square>, open: &[usize]) -> Vec<Placement<Point>> {
+    open.iter()
+        .map(|&id| table.entries[id].placement.clone())
+        .collect()
+}
+
+fn sort(mut ps: Vec<Point>) -> Vec<Point> {
+    ps.sort_by_key(|p| (p.y, p.x));
+    ps
+}
+
+// A MiniZinc model with one boolean per placement and a sum constraint per
+// piece and per open cell, plus the pairs that would put copies out of order
+pub fn to_minizinc(
+    table: &PlacementTable<Square>,
+    board: &Board<Square>,
+) -> (String, Vec<Placement<Point>>) {
+    let (open, _) = table.open_entries(board);
+    let placements = placements(table, &open);
+    let cells = sort(board.remaining().into_iter().cloned().collect());
+
+    let pieces: Vec<String> = open
+        .iter()
+        .map(|&id| (table.entries[id].piece + 1).to_string())
+        .collect();
+    let covers: Vec<String> = open
+        .iter()
+        .map(|&id| {
+            let mut cs: Vec<usize> = table.entries[id]
+                .points
+                .iter()
+                .map(|p| cells.iter().position(|c| c == p).unwrap() + 1)
+                .collect();
+            cs.sort();
+            let cs: Vec<String> = cs.iter().map(|c| c.to_string()).collect();
+            format!("{{{}}}", cs.join(","))
+        })
+        .collect();
+
+    let (before, after): (Vec<String>, Vec<String>) = table
+        .copy_conflicts(&open)
+        .iter()
+        .map(|(a, b)| ((a + 1).to_string(), (b + 1).to_string()))
+        .unzip();
+
+    let mut out = describe("%", table, board, &placements);
+    let _ = writeln!(out, "int: n_placements = {};", open.len());
+    let _ = writeln!(out, "int: n_cells = {};", cells.len());
+    let _ = writeln!(out, "int: n_pieces = {};", table.labels.len());
+    let _ = writeln!(
+        out,
+        "array[1..n_placements] of 1..n_pieces: piece = [{}];",
+        pieces.join(", ")
+    );
+    let _ = writeln!(
+        out,
+        "array[1..n_placements] of set of 1..n_cells: covers = [{}];",
+        covers.join(", ")
+    );
+    let _ = writeln!(out, "int: n_orders = {};", before.len());
+    let _ = writeln!(
+        out,
+        "array[1..n_orders] of 1..n_placements: before = [{}];",
+        before.join(", ")
+    );
+    let _ = writeln!(
+        out,
+        "array[1..n_orders] of 1..n_placements: after = [{}];",
+        after.join(", ")
+    );
+    out.push_str(
+        "array[1..n_placements] of var bool: use;\n\
+         constraint forall(p in 1..n_pieces)(\n\
+         \x20   sum(i in 1..n_placements where piece[i] = p)(bool2int(use[i])) = 1);\n\
+         constraint forall(c in 1..n_cells)(\n\
+         \x20   sum(i in 1..n_placements where c in covers[i])(bool2int(use[i])) = 1);\n\
+         constraint forall(k in 1..n_orders)(not (use[before[k]] /\\ use[after[k]]));\n\
+         solve satisfy;\n\
+         output [\"use = \\(use);\\n\"];\n",
+    );
+
+    (out, placements)
+}
+
+fn sum_is_one(vars: &[usize]) -> String {
+    let terms: Vec<String> = vars.iter().map(|v| format!("(ite p{} 1 0)", v)).collect();
+    format!("(assert (= (+ 0 {}) 1))", terms.join(" "))
+}
+
+// An SMT-LIB 2 script over linear integer arithmetic, one boolean per
+// placement, ending with a request for all of their values
+pub fn to_smtlib(
+    table: &PlacementTable<Square>,
+    board: &Board<Square>,
+) -> (String, Vec<Placement<Point>>) {
+    let (open, position) = table.open_entries(board);
+    let placements = placements(table, &open);
+
+    let mut out = describe(";", table, board, &placements);
+    out.push_str("(set-logic QF_LIA)\n");
+    for i in 1..=open.len() {
+        let _ = writeln!(out, "(declare-const p{} Bool)", i);
+    }
+
+    for (piece, label) in table.labels.iter().enumerate() {
+        let vars: Vec<usize> = (1..=open.len())
+            .filter(|&i| table.entries[open[i - 1]].piece == piece)
+            .collect();
+        let _ = writeln!(out, "; piece {}\n{}", label, sum_is_one(&vars));
+    }
+    for p in sort(board.remaining().into_iter().cloned().collect()) {
+        let cell = table.cell(&p).unwrap();
+        let vars: Vec<usize> = table.by_cell[cell]
+            .iter()
+            .filter_map(|&id| position[id])
+            .map(|i| i + 1)
+            .collect();
+        let _ = writeln!(out, "; cell {},{}\n{}", p.x, p.y, sum_is_one(&vars));
+    }
+    let conflicts = table.copy_conflicts(&open);
+    if !conflicts.is_empty() {
+        out.push_str("; copies of a piece in order\n");
+    }
+    for (a, b) in conflicts {
+        let _ = writeln!(out, "(assert (not (and p{} p{})))", a + 1, b + 1);
+    }
+
+    // get-value needs at least one term, so without placements only check
+    out.push_str("(check-sat)\n");
+    if !open.is_empty() {
+        let names: Vec<String> = (1..=open.len()).map(|i| format!("p{}", i)).collect();
+        let _ = writeln!(out, "(get-value ({}))", names.join(" "));
+    }
+
+    (out, placements)
+}
+
+// Reads the true placement variables from MiniZinc output ("use = [...]"),
+// an SMT solver's get-value or get-model answer, or a SAT model. Ok(None)
+// means the solver found the problem unsatisfiable
+pub fn parse_assignment(text: &str) -> Result<Option<Vec<usize>>, String> {
+    if text.contains("=====UNSATISFIABLE=====") || text.lines().any(|line| line.trim() == "unsat") {
+        return Ok(None);
+    }
+
+    if let Some(start) = text.find("use = [") {
+        let rest = &text[start + "use = [".len()..];
+        let list = &rest[..rest.find(']').ok_or("Unterminated 'use' list.")?];
+        return list
+            .split(',')
+            .enumerate()
+            .filter_map(|(i, value)| match value.trim() {
+                "true" => Some(Ok(i + 1)),
+                "false" => None,
+                other => Some(Err(format!("Unexpected value '{}'.", other))),
+            })
+            .collect::<Result<Vec<_>, String>>()
+            .map(Some);
+    }
+
+    if text.lines().any(|line| line.trim() == "sat") {
+        // pairs like "(p12 true)" or "(define-fun p12 () Bool true)"
+        let tokens: Vec<&str> = text
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .filter(|t| !t.is_empty() && *t != "Bool" && *t != "define-fun")
+            .collect();
+
+        let mut trues = vec![];
+        for pair in tokens.windows(2) {
+            if let (Some(var), "true") = (pair[0].strip_prefix('p'), pair[1]) {
+                if let Ok(var) = var.parse() {
+                    trues.push(var);
+                }
+            }
+        }
+        return Ok(Some(trues));
+    }
+
+    parse_model(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::parse_puzzle;
+
+    #[test]
+    fn smtlib_asks_for_no_values_without_placements() {
+        // the I tetromino has the area of the square but does not fit in it
+        let puzzle = parse_puzzle("board 2\no o\no o\npiece I\n****\n").unwrap();
+        let board = puzzle.make_board(&[]).unwrap();
+        let (script, placements) = to_smtlib(&puzzle.placement_table(), &board);
+        assert!(placements.is_empty());
+        assert!(script.ends_with("(check-sat)\n"));
+        assert!(!script.contains("get-value"));
+    }
+
+    #[test]
+    fn reads_minizinc_output() {
+        let text = "use = [false, true, false, true];\n----------\n";
+        assert_eq!(parse_assignment(text), Ok(Some(vec![2, 4])));
+        assert_eq!(parse_assignment("=====UNSATISFIABLE=====\n"), Ok(None));
+        assert!(parse_assignment("use = [true, maybe]").is_err());
+        assert!(parse_assignment("use = [true").is_err());
+    }
+
+    #[test]
+    fn reads_get_value_answers() {
+        let text = "sat\n((p1 false)\n (p2 true)\n (p3 false)\n (p12 true))\n";
+        assert_eq!(parse_assignment(text), Ok(Some(vec![2, 12])));
+    }
+
+    #[test]
+    fn reads_get_model_answers() {
+        let text = "sat\n(\n  (define-fun p1 () Bool true)\n  (define-fun p2 () Bool false)\n  \
+                    (define-fun p3 () Bool true)\n)\n";
+        assert_eq!(parse_assignment(text), Ok(Some(vec![1, 3])));
+    }
+
+    #[test]
+    fn reads_unsat_answers() {
+        assert_eq!(parse_assignment("unsat\n"), Ok(None));
+        assert_eq!(parse_assignment("s UNSATISFIABLE\n"), Ok(None));
+    }
+}
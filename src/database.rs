@@ -0,0 +1,235 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+use crate::encoding::Solution;
+use crate::rng::Rng;
+
+const MAGIC: &[u8; 4] = b"CPDB";
+const VERSION: u8 = 1;
+
+// Layout of a database file, all numbers little endian:
+//   magic, version, puzzle checksum (u64), pieces per solution (u8),
+//   date count (u32), then per date its labels (count u8, each label as
+//   length u8 + bytes), its solution count (u32) and the offset (u64) of its
+//   first solution in the data section, followed by the data section holding
+//   the fixed size binary encoding of every solution, grouped by date
+pub struct DateEntry {
+    pub labels: Vec<String>,
+    pub count: u32,
+    offset: u64,
+}
+
+pub struct Database {
+    pub checksum: u64,
+    pub dates: Vec<DateEntry>,
+    record_len: u64,
+    data_start: u64,
+    file: BufReader<File>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+// Writes all solutions, per date, with each date's solutions sorted so the
+// same puzzle always produces the same file
+pub fn write(
+    path: &str,
+    checksum: u64,
+    pieces: usize,
+    dates: &[(Vec<String>, Vec<Solution>)],
+) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+
+    w.write_all(MAGIC)?;
+    w.write_all(&[VERSION])?;
+    w.write_all(&checksum.to_le_bytes())?;
+    w.write_all(&[pieces as u8])?;
+    w.write_all(&(dates.len() as u32).to_le_bytes())?;
+
+    let record_len = 2 + 3 * pieces as u64;
+    let mut offset: u64 = 0;
+    for (labels, solutions) in dates {
+        w.write_all(&[labels.len() as u8])?;
+        for label in labels {
+            w.write_all(&[label.len() as u8])?;
+            w.write_all(label.as_bytes())?;
+        }
+        w.write_all(&(solutions.len() as u32).to_le_bytes())?;
+        w.write_all(&offset.to_le_bytes())?;
+        offset += solutions.len() as u64 * record_len;
+    }
+
+    for (_, solutions) in dates {
+        let mut sorted = solutions.clone();
+        sorted.sort();
+        for solution in sorted {
+            w.write_all(&solution.to_bytes())?;
+        }
+    }
+
+    w.flush()
+}
+
+impl Database {
+    pub fn open(path: &str) -> io::Result<Database> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a solution database"));
+        }
+        if read_u8(&mut file)? != VERSION {
+            return Err(invalid("unsupported database version"));
+        }
+
+        let checksum = read_u64(&mut file)?;
+        let record_len = 2 + 3 * read_u8(&mut file)? as u64;
+        let date_count = read_u32(&mut file)?;
+
+        let mut dates = Vec::with_capacity(date_count as usize);
+        for _ in 0..date_count {
+            let label_count = read_u8(&mut file)?;
+            let mut labels = Vec::with_capacity(label_count as usize);
+            for _ in 0..label_count {
+                let mut label = vec![0; read_u8(&mut file)? as usize];
+                file.read_exact(&mut label)?;
+                labels.push(String::from_utf8(label).map_err(|_| invalid("bad label"))?);
+            }
+            let count = read_u32(&mut file)?;
+            let offset = read_u64(&mut file)?;
+            dates.push(DateEntry {
+                labels,
+                count,
+                offset,
+            });
+        }
+
+        let data_start = file.stream_position()?;
+
+        Ok(Database {
+            checksum,
+            dates,
+            record_len,
+            data_start,
+            file,
+        })
+    }
+
+    // Finds the date made up of exactly these labels, in any order
+    pub fn find(&self, labels: &[String]) -> Option<usize> {
+        self.dates.iter().position(|date| {
+            date.labels.len() == labels.len() && labels.iter().all(|l| date.labels.contains(l))
+        })
+    }
+
+    pub fn solution(&mut self, date: usize, i: u32) -> io::Result<Solution> {
+        let entry = &self.dates[date];
+        if i >= entry.count {
+            return Err(invalid("solution index out of range"));
+        }
+
+        let pos = self.data_start + entry.offset + i as u64 * self.record_len;
+        self.file.seek(SeekFrom::Start(pos))?;
+
+        let mut record = vec![0; self.record_len as usize];
+        self.file.read_exact(&mut record)?;
+        Solution::from_bytes(&record).map_err(|e| invalid(&e))
+    }
+
+    pub fn solutions(&mut self, date: usize) -> io::Result<Vec<Solution>> {
+        let entry = &self.dates[date];
+        self.file
+            .seek(SeekFrom::Start(self.data_start + entry.offset))?;
+
+        let mut data = vec![0; (entry.count as u64 * self.record_len) as usize];
+        self.file.read_exact(&mut data)?;
+
+        data.chunks(self.record_len as usize)
+            .map(|record| Solution::from_bytes(record).map_err(|e| invalid(&e)))
+            .collect()
+    }
+
+    // Picks up to n different solutions of a date, each uniformly at random
+    pub fn sample(&mut self, date: usize, n: usize, rng: &mut Rng) -> io::Result<Vec<Solution>> {
+        let count = self.dates[date].count as usize;
+        let mut indices: Vec<usize> = (0..count).collect();
+        let n = n.min(count);
+
+        // partial Fisher-Yates shuffle
+        for i in 0..n {
+            let j = i + rng.below(count - i);
+            indices.swap(i, j);
+        }
+
+        indices[..n]
+            .iter()
+            .map(|&i| self.solution(date, i as u32))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::encode;
+    use crate::hint::place_pieces;
+    use crate::puzzle::{parse_puzzle, Puzzle};
+    use crate::solver::solve_first;
+
+    fn with_groups(groups: &str) -> Puzzle {
+        let text = format!("board 2\nA B o o\nC D o o\n{}piece R\n***\n***\n", groups);
+        parse_puzzle(&text).unwrap()
+    }
+
+    #[test]
+    fn database_opens_again_for_its_own_puzzle_only() {
+        let puzzle = with_groups("group A B\ngroup C D\n");
+        let date = vec!["A".to_string(), "C".to_string()];
+        let mut board = puzzle.make_board(&date).unwrap();
+        let solution = solve_first(board.clone(), puzzle.shapes.clone()).unwrap();
+        place_pieces(&mut board, &puzzle.shapes, &solution).unwrap();
+        let encoded = encode(&board, &puzzle.shapes).unwrap();
+
+        let path = std::env::temp_dir().join(format!("solutions-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let entries = [(date.clone(), vec![encoded.clone()])];
+        write(path, puzzle.checksum(), puzzle.shapes.len(), &entries).unwrap();
+        let mut db = Database::open(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(db.checksum, puzzle.checksum());
+        let found = db.find(&["C".to_string(), "A".to_string()]).unwrap();
+        assert_eq!(db.solutions(found).unwrap(), [encoded]);
+
+        // the same cells, labels and pieces split into other groups make
+        // other puzzles
+        assert_ne!(
+            db.checksum,
+            with_groups("group A B C\ngroup D\n").checksum()
+        );
+        assert_ne!(
+            db.checksum,
+            with_groups("group A\ngroup B C D\n").checksum()
+        );
+    }
+}
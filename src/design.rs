@@ -0,0 +1,330 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::board::make_point_board;
+use crate::geometry::{Point, Shape};
+use crate::puzzle::Puzzle;
+use crate::rng::Rng;
+use crate::solver::{create_solver_with_table, step, StepEvent};
+use crate::table::{create_placement_table, PlacementTable};
+use crate::topology::Square;
+
+// A piece set out of the pool, by index, and the fewest solutions any date
+// has with it (counting stops at the cap)
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub pieces: Vec<usize>,
+    pub min_count: usize,
+}
+
+// How far a search has got, passed to the progress callback after each date
+pub struct Progress {
+    // piece sets or layouts checked so far, and how many there are in all
+    pub sets: u128,
+    pub total: u128,
+    // dates checked for the one being looked at
+    pub dates: usize,
+    pub found: usize,
+    // smallest per-date solution count of the best one found
+    pub best: usize,
+}
+
+pub struct Limits {
+    // stop counting a date's solutions here
+    pub cap: usize,
+    // give up on a date after this many solver steps, treating it as unsolvable
+    pub budget: Option<usize>,
+    // stop after checking this many piece sets or layouts
+    pub sets: Option<usize>,
+}
+
+// Counts a date's solutions, up to `cap`, within the step budget
+fn count_solutions(
+    puzzle: &Puzzle,
+    date: &[String],
+    shapes: Vec<(String, Shape<Point>)>,
+    table: &Arc<PlacementTable<Square>>,
+    limits: &Limits,
+) -> usize {
+    let board = puzzle.make_board(date).unwrap();
+    let mut solver = create_solver_with_table(board, shapes, Arc::clone(table));
+    let mut count = 0;
+    let mut steps = 0;
+
+    while count < limits.cap
+        && limits.budget.is_none_or(|budget| steps < budget)
+        && step(&mut solver, |e, _| {
+            if let StepEvent::Solved = e {
+                count += 1;
+            }
+        })
+    {
+        steps += 1;
+    }
+
+    count
+}
+
+// Solution count of the hardest date, or 0 as soon as a date has none. The
+// date that fails is moved to the front, as a date too hard for one piece
+// set or layout is often too hard for similar ones
+fn check_dates(
+    puzzle: &Puzzle,
+    shapes: &[(String, Shape<Point>)],
+    table: &Arc<PlacementTable<Square>>,
+    dates: &mut Vec<Vec<String>>,
+    limits: &Limits,
+    mut on_date: impl FnMut(usize),
+) -> usize {
+    let mut min_count = usize::MAX;
+
+    for i in 0..dates.len() {
+        let count = count_solutions(puzzle, &dates[i], shapes.to_vec(), table, limits);
+        min_count = min_count.min(count);
+        on_date(i + 1);
+
+        if count == 0 {
+            let date = dates.remove(i);
+            dates.insert(0, date);
+            break;
+        }
+    }
+
+    min_count
+}
+
+// Number of subsets of `areas` adding up to `area`
+fn count_subsets(areas: &[usize], area: usize) -> u128 {
+    let mut ways = vec![0u128; area + 1];
+    ways[0] = 1;
+
+    for &a in areas {
+        for total in (a..=area).rev() {
+            ways[total] += ways[total - a];
+        }
+    }
+
+    ways[area]
+}
+
+// Calls `f` with every subset of indices whose areas add up to `area`, in
+// lexicographic order, until it returns false
+fn for_each_subset(
+    areas: &[usize],
+    area: usize,
+    from: usize,
+    chosen: &mut Vec<usize>,
+    f: &mut impl FnMut(&[usize]) -> bool,
+) -> bool {
+    if area == 0 {
+        return f(chosen);
+    }
+
+    for i in from..areas.len() {
+        if areas[i] <= area {
+            chosen.push(i);
+            let more = for_each_subset(areas, area - areas[i], i + 1, chosen, f);
+            chosen.pop();
+
+            if !more {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+// Searches the pool for piece sets that exactly cover the board on every
+// date, best first. `progress` is called after every date checked
+pub fn design(
+    puzzle: &Puzzle,
+    pool: &[(String, Shape<Point>)],
+    limits: &Limits,
+    mut progress: impl FnMut(Progress),
+) -> Vec<Candidate> {
+    let area = puzzle.cells.len() - puzzle.groups.len();
+    let areas: Vec<usize> = pool.iter().map(|(_, shape)| shape.points.len()).collect();
+    let total = count_subsets(&areas, area);
+
+    // one table for the whole pool serves every piece set
+    let table = create_placement_table(&make_point_board(puzzle.points()), pool);
+    let mut dates = puzzle.dates();
+    let mut candidates = vec![];
+    let mut checked = 0;
+
+    for_each_subset(&areas, area, 0, &mut vec![], &mut |pieces| {
+        let shapes: Vec<(String, Shape<Point>)> = pieces.iter().map(|&i| pool[i].clone()).collect();
+
+        let best = candidates.first().map_or(0, |c: &Candidate| c.min_count);
+        let found = candidates.len();
+        let min_count = check_dates(puzzle, &shapes, &table, &mut dates, limits, |dates| {
+            progress(Progress {
+                sets: checked,
+                total,
+                dates,
+                found,
+                best,
+            })
+        });
+
+        if min_count > 0 {
+            candidates.push(Candidate {
+                pieces: pieces.to_vec(),
+                min_count,
+            });
+            candidates.sort_by_key(|c| std::cmp::Reverse(c.min_count));
+        }
+
+        checked += 1;
+        limits.sets.is_none_or(|sets| checked < sets as u128)
+    });
+
+    candidates
+}
+
+// One change to a layout: a cell moved to a free spot inside the box, or two
+// cells swapping places
+#[derive(Debug, Clone, Copy)]
+enum Move {
+    To(usize, Point),
+    Swap(usize, usize),
+}
+
+fn moves(puzzle: &Puzzle, width: i16, height: i16) -> Vec<Move> {
+    let free: Vec<Point> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| Point { x, y }))
+        .filter(|p| !puzzle.cells.iter().any(|lp| lp.point == *p))
+        .collect();
+
+    let n = puzzle.cells.len();
+    let mut moves = vec![];
+    for i in 0..n {
+        moves.extend(free.iter().map(|p| Move::To(i, *p)));
+        moves.extend((i + 1..n).map(|j| Move::Swap(i, j)));
+    }
+    moves
+}
+
+fn apply(puzzle: &Puzzle, m: Move) -> Puzzle {
+    let mut next = puzzle.clone();
+    match m {
+        Move::To(i, p) => next.cells[i].point = p,
+        Move::Swap(i, j) => {
+            let p = next.cells[i].point;
+            next.cells[i].point = next.cells[j].point;
+            next.cells[j].point = p;
+        }
+    }
+    next
+}
+
+// Where each label is, which is all that tells layouts apart
+fn layout_key(puzzle: &Puzzle) -> Vec<(Point, String)> {
+    let mut key: Vec<(Point, String)> = puzzle
+        .cells
+        .iter()
+        .map(|lp| (lp.point, lp.label.clone()))
+        .collect();
+    key.sort();
+    key
+}
+
+// Random walk over layouts of the puzzle's cells within a width by height
+// box, moving on to a neighbouring layout whenever it is solvable on every
+// date, until no unchecked neighbour is left.
+// `on_layout` gets each such layout with its hardest date's solution count
+// and returns false to stop
+pub fn design_layouts(
+    puzzle: &Puzzle,
+    width: i16,
+    height: i16,
+    limits: &Limits,
+    rng: &mut Rng,
+    mut progress: impl FnMut(Progress),
+    mut on_layout: impl FnMut(&Puzzle, usize) -> bool,
+) {
+    let total = limits.sets.map_or(0, |sets| sets as u128);
+    let mut dates = puzzle.dates();
+    let mut current = puzzle.clone();
+    let mut seen = HashSet::new();
+    let (mut checked, mut found, mut best) = (0, 0, 0);
+
+    seen.insert(layout_key(&current));
+
+    while limits.sets.is_none_or(|sets| checked < sets as u128) {
+        // every layout one move away that has not been checked yet; the walk
+        // is over once there are none
+        let mut unseen: Vec<Puzzle> = moves(&current, width, height)
+            .into_iter()
+            .map(|m| apply(&current, m))
+            .filter(|next| !seen.contains(&layout_key(next)))
+            .collect();
+        if unseen.is_empty() {
+            return;
+        }
+        let next = unseen.swap_remove(rng.below(unseen.len()));
+        seen.insert(layout_key(&next));
+
+        let table = next.placement_table();
+        let min_count = check_dates(&next, &next.shapes, &table, &mut dates, limits, |dates| {
+            progress(Progress {
+                sets: checked,
+                total,
+                dates,
+                found,
+                best,
+            })
+        });
+        checked += 1;
+
+        if min_count > 0 {
+            found += 1;
+            best = best.max(min_count);
+            if !on_layout(&next, min_count) {
+                return;
+            }
+            current = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::parse_puzzle;
+
+    fn walk(text: &str, width: i16, height: i16) -> Vec<Vec<(Point, String)>> {
+        let puzzle = parse_puzzle(text).unwrap();
+        let limits = Limits {
+            cap: 1,
+            budget: None,
+            sets: None,
+        };
+        let mut layouts = vec![];
+        design_layouts(
+            &puzzle,
+            width,
+            height,
+            &limits,
+            &mut Rng::new(1),
+            |_| (),
+            |layout, _| {
+                layouts.push(layout_key(layout));
+                true
+            },
+        );
+        layouts
+    }
+
+    #[test]
+    fn layout_walk_ends_when_every_neighbour_is_checked() {
+        // a domino fits on the three other pairs of neighbouring cells
+        let layouts = walk("board 2\no o\npiece D\n**\n", 2, 2);
+        assert_eq!(layouts.len(), 3);
+        assert_eq!(layouts.iter().collect::<HashSet<_>>().len(), 3);
+
+        // a single cell filling its box cannot move at all
+        assert!(walk("board 2\no\npiece A\n*\n", 1, 1).is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::board::Board;
+use crate::geometry::{variants, Shape};
+use crate::solver::{create_solver_with_table, placements, step, Placement, StepEvent};
+use crate::table::PlacementTable;
+use crate::topology::Topology;
+
+#[derive(Debug, Clone)]
+pub struct Difficulty {
+    pub solutions: usize,
+    // placements the solver makes while enumerating every solution
+    pub nodes: usize,
+    // how many pieces are down on the way to the first solution before some
+    // cell can only be covered one way, if that ever happens
+    pub first_forced: Option<usize>,
+    // higher is harder; infinite when there is no solution
+    pub score: f64,
+}
+
+// Whether some open cell is covered by exactly one placement of the pieces
+// not yet on the board
+fn has_forced<T: Topology>(board: &mut Board<T>, remaining: &[(&str, Vec<Vec<T::Point>>)]) -> bool {
+    let mut ways = HashMap::new();
+    let offsets = board.all.clone();
+
+    for (_, vs) in remaining {
+        for v in vs {
+            for cell in &offsets {
+                let offset = board.topology().offset_for(&v[0], cell);
+                if let Some(covered) = offset.and_then(|offset| board.fill(v, offset, "?")) {
+                    for p in &covered {
+                        *ways.entry(*p).or_insert(0) += 1;
+                    }
+                    board.unfill(covered);
+                }
+            }
+        }
+    }
+
+    board
+        .remaining()
+        .iter()
+        .any(|p| ways.get(*p).copied() == Some(1))
+}
+
+// Replays a solution and returns how many of its placements came before the
+// first forced cell
+fn first_forced<T: Topology>(
+    mut board: Board<T>,
+    shapes: &[(String, Shape<T::Point>)],
+    solution: &[Placement<T::Point>],
+) -> Option<usize> {
+    let topology = board.topology().clone();
+    let mut remaining: Vec<(&str, Vec<Vec<T::Point>>)> = shapes
+        .iter()
+        .map(|(label, shape)| (label.as_str(), variants(&topology, shape)))
+        .collect();
+
+    for (depth, pl) in solution.iter().enumerate() {
+        if has_forced(&mut board, &remaining) {
+            return Some(depth);
+        }
+
+        let i = remaining.iter().position(|(label, _)| *label == pl.label)?;
+        board.fill(&remaining[i].1[pl.variant], pl.offset, &pl.label);
+        remaining.remove(i);
+    }
+
+    None
+}
+
+// Rates a date by enumerating all of its solutions. The score is the number
+// of placements tried per solution, in bits, plus how many pieces have to be
+// placed before the first forced move shows up
+pub fn rate<T: Topology>(
+    board: Board<T>,
+    shapes: Vec<(String, Shape<T::Point>)>,
+    table: Arc<PlacementTable<T>>,
+) -> Difficulty {
+    let start = board.clone();
+    let mut solver = create_solver_with_table(board, shapes.clone(), table);
+    let mut solutions = 0;
+    let mut nodes = 0;
+    let mut first = None;
+
+    loop {
+        let mut solved = false;
+        let more = step(&mut solver, |e, _| match e {
+            StepEvent::FailedToPlace => (),
+            StepEvent::Placed => nodes += 1,
+            StepEvent::Solved => {
+                nodes += 1;
+                solved = true;
+            }
+        });
+
+        if solved {
+            solutions += 1;
+            if first.is_none() {
+                first = Some(placements(&solver));
+            }
+        }
+        if !more {
+            break;
+        }
+    }
+
+    let first_forced = first.and_then(|solution| first_forced(start, &shapes, &solution));
+    let score = if solutions == 0 {
+        f64::INFINITY
+    } else {
+        (nodes as f64 / solutions as f64).log2() + first_forced.unwrap_or(shapes.len()) as f64
+    };
+
+    Difficulty {
+        solutions,
+        nodes,
+        first_forced,
+        score,
+    }
+}
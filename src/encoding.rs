@@ -0,0 +1,191 @@
+use std::fmt;
+
+use crate::board::{make_point_board, Board};
+use crate::geometry::{find_variant, kinds, variants, Point, Shape};
+use crate::stringify::marker;
+use crate::topology::Square;
+
+pub const VERSION: u8 = 1;
+
+// Compact, canonical form of a solution: for every piece, in the order the
+// pieces are listed, the index of its orientation in `variants` and the index
+// of its anchor cell (where the first point of the variant lands) in the
+// board's point list. Copies of a piece take their cells in the order of the
+// first cell of each, as the solver places them, so swapping their labels
+// gives the same encoding
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Solution {
+    pub pieces: Vec<(u8, u16)>,
+}
+
+pub fn encode(
+    board: &Board<Square>,
+    shapes: &[(String, Shape<Point>)],
+) -> Result<Solution, String> {
+    // cells of each piece in board order
+    let mut cells: Vec<Vec<Point>> = shapes
+        .iter()
+        .map(|(label, _)| {
+            let m = marker(label);
+            board
+                .all
+                .iter()
+                .filter(|p| board.at(p).map(marker) == Some(m.clone()))
+                .cloned()
+                .collect()
+        })
+        .collect();
+
+    let kinds = kinds(&Square, shapes);
+    for kind in 0..shapes.len() {
+        let copies: Vec<usize> = (0..shapes.len()).filter(|&i| kinds[i] == kind).collect();
+        let mut taken: Vec<Vec<Point>> = copies.iter().map(|&i| cells[i].clone()).collect();
+        taken.sort_by_key(|ps| ps.first().map(|p| board.all.iter().position(|q| q == p)));
+        for (i, ps) in copies.into_iter().zip(taken) {
+            cells[i] = ps;
+        }
+    }
+
+    let mut pieces = Vec::with_capacity(shapes.len());
+    for ((label, shape), ps) in shapes.iter().zip(&cells) {
+        let (variant, offset) = find_variant(&Square, &variants(&Square, shape), ps)
+            .ok_or_else(|| format!("Piece {} is not placed.", label))?;
+        let anchor = board.all.iter().position(|p| *p == offset).unwrap();
+
+        pieces.push((variant as u8, anchor as u16));
+    }
+
+    Ok(Solution { pieces })
+}
+
+// Rebuilds the solved board, blocking out whatever the pieces leave uncovered
+pub fn decode(
+    solution: &Solution,
+    points: &[Point],
+    shapes: &[(String, Shape<Point>)],
+) -> Result<Board<Square>, String> {
+    if solution.pieces.len() != shapes.len() {
+        return Err(format!(
+            "Solution has {} pieces, expected {}.",
+            solution.pieces.len(),
+            shapes.len()
+        ));
+    }
+
+    let mut board = make_point_board(points.to_vec());
+
+    for ((label, shape), (variant, anchor)) in shapes.iter().zip(&solution.pieces) {
+        let vs = variants(&Square, shape);
+        let v = vs
+            .get(*variant as usize)
+            .ok_or_else(|| format!("Piece {} has no orientation {}.", label, variant))?;
+        let offset = *points
+            .get(*anchor as usize)
+            .ok_or_else(|| format!("No cell {} on the board.", anchor))?;
+
+        if board.fill(v, offset, label).is_none() {
+            return Err(format!("Piece {} does not fit.", label));
+        }
+    }
+
+    let open: Vec<Point> = board.remaining().into_iter().cloned().collect();
+    board.fill(&open, Point { x: 0, y: 0 }, "*");
+
+    Ok(board)
+}
+
+impl Solution {
+    // version, piece count, then per piece the orientation and the anchor
+    // (little endian)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + 3 * self.pieces.len());
+        bytes.push(VERSION);
+        bytes.push(self.pieces.len() as u8);
+
+        for (variant, anchor) in &self.pieces {
+            bytes.push(*variant);
+            bytes.extend_from_slice(&anchor.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Solution, String> {
+        match bytes {
+            [VERSION, n, rest @ ..] if rest.len() == 3 * *n as usize => Ok(Solution {
+                pieces: rest
+                    .chunks(3)
+                    .map(|c| (c[0], u16::from_le_bytes([c[1], c[2]])))
+                    .collect(),
+            }),
+            [VERSION, ..] => Err("Truncated solution.".to_string()),
+            _ => Err("Unsupported solution version.".to_string()),
+        }
+    }
+
+    // Text form is "1:o.a,o.a,..." with one orientation/anchor pair per piece
+    pub fn from_text(s: &str) -> Result<Solution, String> {
+        let (version, rest) = s.trim().split_once(':').ok_or("Missing version.")?;
+        if version.parse::<u8>() != Ok(VERSION) {
+            return Err("Unsupported solution version.".to_string());
+        }
+
+        let pieces = rest
+            .split(',')
+            .map(|piece| {
+                let (variant, anchor) = piece.split_once('.')?;
+                Some((variant.parse().ok()?, anchor.parse().ok()?))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("Malformed solution '{}'.", s))?;
+
+        Ok(Solution { pieces })
+    }
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pieces: Vec<String> = self
+            .pieces
+            .iter()
+            .map(|(variant, anchor)| format!("{}.{}", variant, anchor))
+            .collect();
+
+        write!(f, "{}:{}", VERSION, pieces.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hint::place_pieces;
+    use crate::puzzle::parse_puzzle;
+    use crate::solver::solve_first;
+    use crate::verify::verify;
+
+    #[test]
+    fn copies_encode_the_same_either_way_round() {
+        let puzzle = parse_puzzle("board 2\no o o o\no o o o\npiece L x2\n***\n*\n").unwrap();
+        let labels: Vec<&str> = puzzle.shapes.iter().map(|(l, _)| l.as_str()).collect();
+        assert_eq!(labels, ["L", "l"]);
+
+        let mut board = puzzle.make_board(&[]).unwrap();
+        let solution = solve_first(board.clone(), puzzle.shapes.clone()).unwrap();
+        place_pieces(&mut board, &puzzle.shapes, &solution).unwrap();
+
+        // the same tiling with the copies' labels swapped
+        let mut swapped = puzzle.make_board(&[]).unwrap();
+        for p in &board.all {
+            let m = if board.at(p) == Some("L") { "l" } else { "L" };
+            swapped.fill(&vec![*p], Point { x: 0, y: 0 }, m);
+        }
+
+        assert!(verify(&board, &puzzle.shapes, &[]).is_empty());
+        assert!(verify(&swapped, &puzzle.shapes, &[]).is_empty());
+        let encoded = encode(&board, &puzzle.shapes).unwrap();
+        assert_eq!(encode(&swapped, &puzzle.shapes).unwrap(), encoded);
+
+        let decoded = decode(&encoded, &puzzle.points(), &puzzle.shapes).unwrap();
+        assert_eq!(encode(&decoded, &puzzle.shapes).unwrap(), encoded);
+    }
+}
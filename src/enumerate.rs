@@ -0,0 +1,176 @@
+use std::collections::BTreeSet;
+
+use crate::geometry::{canonical, variants, Shape, ShapeAttrs};
+use crate::topology::Topology;
+
+// Which moves make two pieces the same: translations only, also rotations,
+// or also reflections
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    Fixed,
+    OneSided,
+    Free,
+}
+
+fn shape_with<P: Copy>(ps: &[P], chiral: bool, rotations: usize) -> Shape<P> {
+    Shape {
+        points: ps.to_vec(),
+        attrs: ShapeAttrs {
+            chiral,
+            rotations: rotations as u8,
+        },
+    }
+}
+
+// The smallest canonical form among the orientations the symmetry allows, so
+// that pieces equal under it share a key
+fn key<T: Topology>(topology: &T, ps: &[T::Point], symmetry: Symmetry) -> Vec<T::Point> {
+    let all = topology.rotations() - 1;
+    let shape = match symmetry {
+        Symmetry::Fixed => shape_with(ps, false, 0),
+        Symmetry::OneSided => shape_with(ps, false, all),
+        Symmetry::Free => shape_with(ps, true, all),
+    };
+
+    variants(topology, &shape)
+        .iter()
+        .map(|v| canonical(topology, v))
+        .min()
+        .unwrap()
+}
+
+// Attributes that make `variants` produce each orientation once: rotations
+// up to the shape's period, and flipping only for free pieces that differ
+// from their mirror image
+pub fn attrs<T: Topology>(topology: &T, ps: &[T::Point], symmetry: Symmetry) -> ShapeAttrs {
+    if symmetry == Symmetry::Fixed {
+        return ShapeAttrs {
+            chiral: false,
+            rotations: 0,
+        };
+    }
+
+    let all = topology.rotations() - 1;
+    let turned = variants(topology, &shape_with(ps, false, all)).len();
+    let chiral =
+        symmetry == Symmetry::Free && variants(topology, &shape_with(ps, true, all)).len() > turned;
+
+    ShapeAttrs {
+        chiral,
+        rotations: (turned - 1) as u8,
+    }
+}
+
+// All connected pieces of `n` cells, grown one cell at a time from `cell`
+// and its rotations. Growing only one representative of each piece still
+// reaches every larger piece, as each has a cell whose removal leaves it
+// connected
+pub fn polyforms<T: Topology>(
+    topology: &T,
+    cell: T::Point,
+    n: usize,
+    symmetry: Symmetry,
+) -> Vec<Shape<T::Point>> {
+    if n == 0 {
+        return vec![];
+    }
+
+    let mut found: BTreeSet<Vec<T::Point>> = (0..topology.rotations())
+        .map(|i| key(topology, &[topology.rotate(&cell, i)], symmetry))
+        .collect();
+
+    for _ in 1..n {
+        let mut next = BTreeSet::new();
+
+        for ps in &found {
+            for p in ps {
+                for q in topology.neighbours(p) {
+                    if ps.contains(&q) {
+                        continue;
+                    }
+
+                    let mut grown = ps.clone();
+                    grown.push(q);
+                    next.insert(key(topology, &grown, symmetry));
+                }
+            }
+        }
+
+        found = next;
+    }
+
+    found
+        .into_iter()
+        .map(|ps| Shape {
+            attrs: attrs(topology, &ps, symmetry),
+            points: ps,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{HexPoint, Point, TriPoint};
+    use crate::topology::{Hex, Square, Tri};
+
+    fn counts<T: Topology>(topology: &T, cell: T::Point, symmetry: Symmetry) -> Vec<usize> {
+        (1..=8)
+            .map(|n| polyforms(topology, cell, n, symmetry).len())
+            .collect()
+    }
+
+    // OEIS A000105, A000988 and A001168
+    #[test]
+    fn polyominoes() {
+        let cell = Point { x: 0, y: 0 };
+        assert_eq!(
+            counts(&Square, cell, Symmetry::Free),
+            [1, 1, 2, 5, 12, 35, 108, 369]
+        );
+        assert_eq!(
+            counts(&Square, cell, Symmetry::OneSided),
+            [1, 1, 2, 7, 18, 60, 196, 704]
+        );
+        assert_eq!(
+            counts(&Square, cell, Symmetry::Fixed),
+            [1, 2, 6, 19, 63, 216, 760, 2725]
+        );
+    }
+
+    // OEIS A000228, A006535 and A001207
+    #[test]
+    fn polyhexes() {
+        let cell = HexPoint { q: 0, r: 0 };
+        assert_eq!(
+            counts(&Hex, cell, Symmetry::Free),
+            [1, 1, 3, 7, 22, 82, 333, 1448]
+        );
+        assert_eq!(
+            counts(&Hex, cell, Symmetry::OneSided),
+            [1, 1, 3, 10, 33, 147, 620, 2821]
+        );
+        assert_eq!(
+            counts(&Hex, cell, Symmetry::Fixed),
+            [1, 3, 11, 44, 186, 814, 3652, 16689]
+        );
+    }
+
+    // OEIS A000577, A006534 and A001420
+    #[test]
+    fn polyiamonds() {
+        let cell = TriPoint::new(0, 0, true);
+        assert_eq!(
+            counts(&Tri, cell, Symmetry::Free),
+            [1, 1, 1, 3, 4, 12, 24, 66]
+        );
+        assert_eq!(
+            counts(&Tri, cell, Symmetry::OneSided),
+            [1, 1, 1, 4, 6, 19, 43, 120]
+        );
+        assert_eq!(
+            counts(&Tri, cell, Symmetry::Fixed),
+            [2, 3, 6, 14, 36, 94, 250, 675]
+        );
+    }
+}
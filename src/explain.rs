@@ -0,0 +1,217 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::board::Board;
+use crate::geometry::{Point, Shape};
+use crate::solver::{create_solver_with_table, step, StepEvent};
+use crate::table::PlacementTable;
+use crate::topology::{Square, Topology};
+
+// Why a board cannot be tiled, from the cheapest argument that applies
+pub enum Reason {
+    AreaMismatch { cells: usize, pieces: usize },
+    Uncoverable(Vec<Point>),
+    IsolatedRegion(Vec<Point>),
+    Parity { dark: usize, light: usize },
+    Exhausted { nodes: usize, elapsed: Duration },
+}
+
+fn format_points(ps: &[Point]) -> String {
+    let ps: Vec<String> = ps.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+    ps.join(" ")
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::AreaMismatch { cells, pieces } => write!(
+                f,
+                "There are {} open cells but the pieces cover {}.",
+                cells, pieces
+            ),
+            Reason::Uncoverable(ps) => write!(
+                f,
+                "No piece can be placed over the cells at {}.",
+                format_points(ps)
+            ),
+            Reason::IsolatedRegion(ps) => write!(
+                f,
+                "The {} cells at {} are cut off, and no choice of pieces fits them exactly.",
+                ps.len(),
+                format_points(ps)
+            ),
+            Reason::Parity { dark, light } => write!(
+                f,
+                "Coloured like a checkerboard the open cells are {} dark and {} light, a \
+                 difference no arrangement of the pieces can make up.",
+                dark, light
+            ),
+            Reason::Exhausted { nodes, elapsed } => write!(
+                f,
+                "Exhaustive search found no tiling ({} placements tried in {:.1}s).",
+                nodes,
+                elapsed.as_secs_f64()
+            ),
+        }
+    }
+}
+
+// Every way each piece can go on the board, as the cells it covers
+fn placements(table: &PlacementTable<Square>, board: &Board<Square>) -> Vec<Vec<Vec<Point>>> {
+    let (open, _) = table.open_entries(board);
+    let mut covers = vec![vec![]; table.labels.len()];
+    for id in open {
+        let entry = &table.entries[id];
+        covers[entry.piece].push(entry.points.clone());
+    }
+    covers
+}
+
+// Sums that some subset of the sizes adds up to
+fn subset_sums(sizes: &[usize]) -> HashSet<usize> {
+    let mut sums = HashSet::from([0]);
+    for size in sizes {
+        let more: Vec<usize> = sums.iter().map(|s| s + size).collect();
+        sums.extend(more);
+    }
+    sums
+}
+
+// Open cells split into groups connected through open neighbours
+fn regions(board: &Board<Square>) -> Vec<Vec<Point>> {
+    let mut seen = HashSet::new();
+    let mut regions = vec![];
+
+    for start in &board.all {
+        if board.at(start).is_some() || seen.contains(start) {
+            continue;
+        }
+
+        let mut region = vec![];
+        let mut todo = vec![*start];
+        seen.insert(*start);
+        while let Some(p) = todo.pop() {
+            region.push(p);
+            for n in Square.neighbours(&p) {
+                if board.all.contains(&n) && board.at(&n).is_none() && seen.insert(n) {
+                    todo.push(n);
+                }
+            }
+        }
+
+        region.sort_by_key(|p| (p.y, p.x));
+        regions.push(region);
+    }
+
+    regions
+}
+
+// Whether +/- each difference can add up to the target
+fn signed_sum(diffs: &[i32], target: i32) -> bool {
+    let mut sums = HashSet::from([0]);
+    for d in diffs {
+        sums = sums.iter().flat_map(|s| [s + d, s - d]).collect();
+    }
+    sums.contains(&target)
+}
+
+fn run_search(
+    table: &Arc<PlacementTable<Square>>,
+    board: &Board<Square>,
+    shapes: &[(String, Shape<Point>)],
+) -> Option<Reason> {
+    let started = Instant::now();
+    let mut solver = create_solver_with_table(board.clone(), shapes.to_vec(), Arc::clone(table));
+    let mut nodes = 0;
+    let mut solved = false;
+
+    while !solved
+        && step(&mut solver, |e, _| match e {
+            StepEvent::FailedToPlace => (),
+            StepEvent::Placed => nodes += 1,
+            StepEvent::Solved => solved = true,
+        })
+    {}
+
+    if solved {
+        None
+    } else {
+        Some(Reason::Exhausted {
+            nodes,
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+// Finds out why the pieces cannot cover the open cells of the board, or
+// returns None if they can. The table has to hold exactly these pieces
+pub fn explain(
+    table: &Arc<PlacementTable<Square>>,
+    board: &Board<Square>,
+    shapes: &[(String, Shape<Point>)],
+) -> Option<Reason> {
+    let open: Vec<Point> = board
+        .all
+        .iter()
+        .filter(|p| board.at(p).is_none())
+        .cloned()
+        .collect();
+    let sizes: Vec<usize> = shapes.iter().map(|(_, shape)| shape.points.len()).collect();
+
+    let area: usize = sizes.iter().sum();
+    if area != open.len() {
+        return Some(Reason::AreaMismatch {
+            cells: open.len(),
+            pieces: area,
+        });
+    }
+
+    let placements = placements(table, board);
+    let covered: HashSet<&Point> = placements.iter().flatten().flatten().collect();
+    let uncoverable: Vec<Point> = open
+        .iter()
+        .filter(|p| !covered.contains(p))
+        .cloned()
+        .collect();
+    if !uncoverable.is_empty() {
+        return Some(Reason::Uncoverable(uncoverable));
+    }
+
+    // a region can only take pieces that fit inside it
+    for region in regions(board) {
+        let inside: HashSet<&Point> = region.iter().collect();
+        let fitting: Vec<usize> = placements
+            .iter()
+            .zip(&sizes)
+            .filter(|(covers, _)| {
+                covers
+                    .iter()
+                    .any(|ps| ps.iter().all(|p| inside.contains(p)))
+            })
+            .map(|(_, size)| *size)
+            .collect();
+
+        if !subset_sums(&fitting).contains(&region.len()) {
+            return Some(Reason::IsolatedRegion(region));
+        }
+    }
+
+    // moving a piece by one cell swaps the colours it covers, so each piece
+    // adds its own imbalance one way or the other
+    let dark = open.iter().filter(|p| (p.x + p.y) % 2 == 0).count();
+    let light = open.len() - dark;
+    let diffs: Vec<i32> = shapes
+        .iter()
+        .map(|(_, shape)| {
+            let d = shape.points.iter().filter(|p| (p.x + p.y) % 2 == 0).count() as i32;
+            2 * d - shape.points.len() as i32
+        })
+        .collect();
+    if !signed_sum(&diffs, dark as i32 - light as i32) {
+        return Some(Reason::Parity { dark, light });
+    }
+
+    run_search(table, board, shapes)
+}
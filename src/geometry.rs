@@ -1,10 +1,57 @@
-#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
+use std::collections::HashSet;
+
+use crate::topology::Topology;
+
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
 pub struct Point {
     pub x: i16,
     pub y: i16,
 }
 
-#[derive(Debug)]
+// Axial coordinates on a hexagonal grid, r growing down and to the right
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+pub struct HexPoint {
+    pub q: i16,
+    pub r: i16,
+}
+
+// A triangle of the triangular lattice, stored as three times its centroid
+// with the upward triangle in lattice cell (0, 0) at the origin, so upward
+// triangles have both coordinates divisible by 3 and downward ones are 1 more
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+pub struct TriPoint {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl TriPoint {
+    // The upward or downward triangle of lattice cell (a, b)
+    pub fn new(a: i16, b: i16, up: bool) -> Self {
+        let d = if up { 0 } else { 1 };
+        TriPoint {
+            x: 3 * a + d,
+            y: 3 * b + d,
+        }
+    }
+
+    pub fn up(&self) -> bool {
+        self.x.rem_euclid(3) == 0
+    }
+
+    pub fn cell(&self) -> (i16, i16) {
+        (self.x.div_euclid(3), self.y.div_euclid(3))
+    }
+}
+
+// A unit cube in space, z counting layers from the bottom
+#[derive(Debug, Hash, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+pub struct Point3 {
+    pub x: i16,
+    pub y: i16,
+    pub z: i16,
+}
+
+#[derive(Debug, Clone)]
 pub struct LabeledPoint<P> {
     pub label: String,
     pub point: P,
@@ -15,7 +62,7 @@ pub type LabeledPoints<P> = Vec<LabeledPoint<P>>;
 #[derive(Clone, Debug)]
 pub struct ShapeAttrs {
     pub chiral: bool,
-    pub rotations: u8, // Since Rust doesn't have union types, we'll use u8 to represent 0, 1, or 3 rotations
+    pub rotations: u8, // how many rotations past the identity give new orientations, e.g. 0, 1 or 3 on a square grid
 }
 
 #[derive(Debug)]
@@ -30,39 +77,89 @@ pub struct Shape<P: Clone> {
     pub attrs: ShapeAttrs, // Storing ShapeAttrs within Shape
 }
 
-type Mapper = fn(Point) -> Point;
-
-fn flip_point(p: Point) -> Point {
-    Point { x: -p.x, y: p.y }
-}
-
-fn identity(p: Point) -> Point {
-    p
+// Same points in the same place up to translation give the same result
+pub fn canonical<T: Topology>(topology: &T, ps: &[T::Point]) -> Vec<T::Point> {
+    let mut key = ps.to_vec();
+    key.sort();
+    topology.normalize(&key)
 }
 
-const ROTATES: [Mapper; 4] = [
-    identity,                              // 0 degrees rotation
-    |p: Point| Point { x: -p.y, y: p.x },  // 90 degrees rotation
-    |p: Point| Point { x: -p.x, y: -p.y }, // 180 degrees rotation
-    |p: Point| Point { x: p.y, y: -p.x },  // 270 degrees rotation
-];
-
-pub fn variants(shape: &Shape<Point>) -> Vec<Vec<Point>> {
-    let flips: Vec<Mapper> = if shape.attrs.chiral {
-        vec![identity, flip_point]
+// All distinct orientations of a shape: the first `rotations` + 1 elements
+// of the rotation group, and the same again for the reflected shape if it is
+// chiral, each translated so its first point is at the origin
+pub fn variants<T: Topology>(topology: &T, shape: &Shape<T::Point>) -> Vec<Vec<T::Point>> {
+    let flips = if shape.attrs.chiral {
+        vec![false, true]
     } else {
-        vec![identity]
+        vec![false]
     };
 
-    let mut vs = Vec::with_capacity(shape.attrs.rotations as usize * flips.len());
+    let mut vs = Vec::with_capacity((shape.attrs.rotations as usize + 1) * flips.len());
+    let mut seen = HashSet::new();
 
     for &flip in &flips {
-        for i in 0..=shape.attrs.rotations {
-            let rotate = ROTATES[(i as usize) % 4];
-            let v: Vec<Point> = shape.points.iter().map(|&p| rotate(flip(p))).collect();
-            vs.push(v);
+        for i in 0..=shape.attrs.rotations as usize {
+            let v: Vec<T::Point> = shape
+                .points
+                .iter()
+                .map(|p| {
+                    let p = if flip { topology.reflect(p) } else { *p };
+                    topology.rotate(&p, i % topology.rotations())
+                })
+                .collect();
+            let v = topology.normalize(&v);
+
+            // skip orientations that only differ by translation
+            if seen.insert(canonical(topology, &v)) {
+                vs.push(v);
+            }
         }
     }
 
     vs
 }
+
+// For each piece the first one with the same orientations; copies of a piece
+// share it and can stand in for each other
+pub fn kinds<T: Topology>(topology: &T, shapes: &[(String, Shape<T::Point>)]) -> Vec<usize> {
+    let keys: Vec<Vec<Vec<T::Point>>> = shapes
+        .iter()
+        .map(|(_, shape)| {
+            let mut key: Vec<Vec<T::Point>> = variants(topology, shape)
+                .iter()
+                .map(|v| canonical(topology, v))
+                .collect();
+            key.sort();
+            key
+        })
+        .collect();
+
+    keys.iter()
+        .map(|key| keys.iter().position(|k| k == key).unwrap())
+        .collect()
+}
+
+// Finds which variant, and at which offset, covers exactly the given points
+pub fn find_variant<T: Topology>(
+    topology: &T,
+    vs: &[Vec<T::Point>],
+    ps: &[T::Point],
+) -> Option<(usize, T::Point)> {
+    let target: HashSet<&T::Point> = ps.iter().collect();
+
+    for (i, v) in vs.iter().enumerate() {
+        if v.len() != ps.len() {
+            continue;
+        }
+
+        for offset in ps.iter().filter_map(|p| topology.offset_for(&v[0], p)) {
+            let covers = v.iter().all(|p| target.contains(&topology.add(p, &offset)));
+
+            if covers {
+                return Some((i, offset));
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,103 @@
+use crate::board::Board;
+use crate::geometry::{variants, Point, Shape};
+use crate::solver::{solve_first, Placement};
+use crate::topology::Square;
+
+pub enum Hint {
+    Solved,
+    Place(Placement<Point>),
+    Remove(String),
+    Unsolvable,
+}
+
+// Puts pieces the user has already placed onto the board, checking that they fit
+pub fn place_pieces(
+    board: &mut Board<Square>,
+    shapes: &[(String, Shape<Point>)],
+    placed: &[Placement<Point>],
+) -> Result<(), String> {
+    for (i, pl) in placed.iter().enumerate() {
+        if placed[..i].iter().any(|other| other.label == pl.label) {
+            return Err(format!("Piece '{}' is placed more than once.", pl.label));
+        }
+
+        let shape = shapes
+            .iter()
+            .find(|(label, _)| *label == pl.label)
+            .map(|(_, shape)| shape)
+            .ok_or_else(|| format!("Piece '{}' not found.", pl.label))?;
+
+        let vs = variants(&Square, shape);
+        let v = vs.get(pl.variant).ok_or_else(|| {
+            format!(
+                "Piece '{}' has no orientation {} (0-{} available).",
+                pl.label,
+                pl.variant,
+                vs.len() - 1
+            )
+        })?;
+
+        if board.fill(v, pl.offset, &pl.label).is_none() {
+            return Err(format!(
+                "Piece '{}' does not fit at {},{}.",
+                pl.label, pl.offset.x, pl.offset.y
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn unplaced(
+    shapes: &[(String, Shape<Point>)],
+    placed: &[Placement<Point>],
+) -> Vec<(String, Shape<Point>)> {
+    shapes
+        .iter()
+        .filter(|(label, _)| !placed.iter().any(|pl| pl.label == *label))
+        .cloned()
+        .collect()
+}
+
+fn solvable(
+    board: &Board<Square>,
+    shapes: &[(String, Shape<Point>)],
+    placed: &[Placement<Point>],
+) -> Result<Option<Vec<Placement<Point>>>, String> {
+    let mut b = board.clone();
+    place_pieces(&mut b, shapes, placed)?;
+
+    let remaining = unplaced(shapes, placed);
+    if remaining.is_empty() {
+        return Ok(Some(vec![]));
+    }
+
+    Ok(solve_first(b, remaining))
+}
+
+// Suggests a single next placement that still leads to a solution, or which
+// already placed piece has to go when the partial state is a dead end
+pub fn hint(
+    board: &Board<Square>,
+    shapes: &[(String, Shape<Point>)],
+    placed: &[Placement<Point>],
+) -> Result<Hint, String> {
+    if let Some(rest) = solvable(board, shapes, placed)? {
+        return Ok(match rest.into_iter().next() {
+            Some(next) => Hint::Place(next),
+            None => Hint::Solved,
+        });
+    }
+
+    // try taking back the most recently placed pieces first
+    for i in (0..placed.len()).rev() {
+        let mut fewer = placed.to_vec();
+        let removed = fewer.remove(i);
+
+        if solvable(board, shapes, &fewer)?.is_some() {
+            return Ok(Hint::Remove(removed.label));
+        }
+    }
+
+    Ok(Hint::Unsolvable)
+}
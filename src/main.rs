@@ -3,169 +3,1124 @@ mod geometry;
 use geometry::Point;
 
 mod board;
+mod cdcl;
+mod constraint;
 use board::make_point_board;
+use board::make_wrapped_board;
 mod parallel;
 use parallel::create_parallel_solver;
-mod stringify;
-use geometry::VisualShape;
+use parallel::run_jobs;
+use parallel::run_parallel;
 mod solver;
+mod stringify;
+mod topology;
 use solver::create_solver;
-use stringify::convert_to_labeled_points;
-use stringify::convert_to_shape;
+use stringify::convert_cube_to_strings;
+use stringify::convert_hex_to_strings;
 use stringify::convert_to_strings;
+use stringify::convert_tri_to_strings;
+use stringify::convert_wrapped_to_strings;
+use stringify::format_placement;
+use stringify::parse_board;
+use stringify::parse_placement;
+use topology::{Cube, Hex, Square, Tri, Wrapped};
+mod database;
+mod design;
+mod difficulty;
+mod encoding;
+mod enumerate;
+mod explain;
+mod hint;
+mod matrix;
+mod memo;
+mod order;
+mod puzzle;
+mod rng;
+mod sat;
+mod svg;
+mod symmetry;
+mod table;
+mod verify;
+
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+// Prints a board on any grid, drawn by `to_strings` with the first character
+// of each marker and '-' for open cells
+fn print_with<T: topology::Topology>(
+    board: &board::Board<T>,
+    to_strings: impl Fn(&[T::Point], &dyn Fn(&T::Point) -> char) -> Vec<String>,
+) {
+    let to_char = |p: &T::Point| match board.at(p) {
+        Some(marker) => marker.chars().next().unwrap_or(' '),
+        None => '-',
+    };
+
+    println!("{}", to_strings(&board.all, &to_char).join("\n"));
+}
+
+fn print_board(board: &board::Board<Square>) {
+    print_with(board, |ps, to_char| convert_to_strings(ps, to_char));
+}
 
-use std::sync::{mpsc, Arc, Mutex};
-use std::thread;
+fn print_solution(
+    board: &board::Board<Square>,
+    shapes: &[(String, geometry::Shape<Point>)],
+    encode: bool,
+) {
+    print_board(board);
+
+    if encode {
+        if let Ok(solution) = encoding::encode(board, shapes) {
+            println!("{}", solution);
+        }
+    }
+}
 
-fn print_board(points: &[Point], board: &board::Board<Point>) {
-    let board_strs = convert_to_strings(points, |p| {
-        if let Some(marker) = board.at(p) {
-            marker.chars().next().unwrap_or(' ')
+fn run_hint(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let mut labels = vec![];
+    let mut placed = vec![];
+    for arg in args {
+        if let Some(pl) = parse_placement(arg) {
+            placed.push(pl);
         } else {
-            '-'
+            labels.push(arg.clone());
         }
-    });
+    }
+
+    let board = puzzle.make_board(&labels).unwrap_or_else(|e| exit_with(e));
 
-    println!("{}", board_strs.join("\n"));
+    match hint::hint(&board, &puzzle.shapes, &placed) {
+        Ok(hint::Hint::Solved) => println!("Already solved."),
+        Ok(hint::Hint::Place(next)) => {
+            println!("Hint: place {}", format_placement(&next));
+            placed.push(next);
+
+            let mut b = board.clone();
+            if hint::place_pieces(&mut b, &puzzle.shapes, &placed).is_ok() {
+                print_board(&b);
+            }
+        }
+        Ok(hint::Hint::Remove(label)) => {
+            println!("Dead end: you must remove piece {}.", label)
+        }
+        Ok(hint::Hint::Unsolvable) => {
+            if placed.is_empty() {
+                println!("No solution exists.");
+            } else {
+                println!("Dead end: removing a single piece does not help, start over.");
+            }
+        }
+        Err(e) => exit_with(e),
+    }
 }
 
-fn to_strings(ps: &[&str]) -> Vec<String> {
-    ps.iter().map(|s| s.to_string()).collect()
+fn read_input(path: &str) -> String {
+    let text = if path == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map(|_| text)
+    } else {
+        std::fs::read_to_string(path)
+    };
+
+    text.unwrap_or_else(|e| exit_with(format!("Could not read {}: {}", path, e)))
 }
 
-fn main() {
-    let board_pts = convert_to_labeled_points(
-        &[
-            "Jan Feb Mar Apr May Jun ",
-            "Jul Aug Sep Oct Nov Dec ",
-            "  1   2   3   4   5   6   7 ",
-            "  8   9  10  11  12  13  14 ",
-            " 15  16  17  18  19  20  21 ",
-            " 22  23  24  25  26  27  28 ",
-            " 29  30  31 Sun Mon Tue Wed ",
-            "                Thu Fri Sat ",
-        ],
-        4,
-    );
+fn read_board(puzzle: &puzzle::Puzzle, path: &str) -> board::Board<Square> {
+    let text = read_input(path);
+    let lines: Vec<&str> = text
+        .lines()
+        .skip_while(|line| line.trim().is_empty() || *line == "Solved!")
+        .collect();
 
-    let points: Vec<Point> = board_pts.iter().map(|lp| lp.point).collect();
-    let mut board = make_point_board(points);
+    parse_board(&lines, &puzzle.points()).unwrap_or_else(|e| exit_with(e))
+}
 
-    let l_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&[
-            "***", //
-            "*",   //
-        ]),
-        attrs: geometry::ShapeAttrs {
-            chiral: true,
-            rotations: 3,
-        },
-    });
+fn run_encode(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let binary = args.first().map(|s| s.as_str()) == Some("--binary");
+    let args = if binary { &args[1..] } else { args };
+    if args.len() != 1 {
+        exit_with("Usage: encode [--binary] <file|->");
+    }
 
-    let j_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&[
-            "****", //
-            "*",    //
-        ]),
-        attrs: geometry::ShapeAttrs {
-            chiral: true,
-            rotations: 3,
-        },
-    });
+    let board = read_board(puzzle, &args[0]);
+    match encoding::encode(&board, &puzzle.shapes) {
+        Ok(solution) if binary => {
+            let _ = std::io::stdout().write_all(&solution.to_bytes());
+        }
+        Ok(solution) => println!("{}", solution),
+        Err(e) => exit_with(e),
+    }
+}
 
-    let i_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&["****"]),
-        attrs: geometry::ShapeAttrs {
-            chiral: false,
-            rotations: 1,
-        },
-    });
+fn run_decode(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let solutions = if args.first().map(|s| s.as_str()) == Some("--binary") {
+        let mut bytes = vec![];
+        let read = match args.get(1).map(|s| s.as_str()) {
+            None | Some("-") => std::io::stdin().read_to_end(&mut bytes).map(|_| ()),
+            Some(path) => std::fs::read(path).map(|b| bytes = b),
+        };
+        if let Err(e) = read {
+            exit_with(format!("Could not read input: {}", e));
+        }
 
-    let p_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&[
-            "***", //
-            "**",  //
-        ]),
-        attrs: geometry::ShapeAttrs {
-            chiral: true,
-            rotations: 3,
-        },
-    });
+        // records are self delimiting: version, piece count, 3 bytes per piece
+        let mut solutions = vec![];
+        let mut rest = &bytes[..];
+        while rest.len() >= 2 {
+            let len = (2 + 3 * rest[1] as usize).min(rest.len());
+            solutions.push(encoding::Solution::from_bytes(&rest[..len]));
+            rest = &rest[len..];
+        }
+        solutions
+    } else {
+        args.iter()
+            .map(|arg| encoding::Solution::from_text(arg))
+            .collect()
+    };
 
-    let n_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&[
-            "**",   //
-            " ***", //
-        ]),
-        attrs: geometry::ShapeAttrs {
-            chiral: true,
-            rotations: 3,
-        },
-    });
+    for solution in solutions {
+        let board = solution
+            .and_then(|solution| encoding::decode(&solution, &puzzle.points(), &puzzle.shapes))
+            .unwrap_or_else(|e| exit_with(e));
+        print_board(&board);
+    }
+}
 
-    let u_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&[
-            "* *", //
-            "***", //
-        ]),
-        attrs: geometry::ShapeAttrs {
-            chiral: false,
-            rotations: 3,
-        },
-    });
+fn run_verify(puzzle: &puzzle::Puzzle, args: &[String]) {
+    if args.is_empty() {
+        exit_with("Usage: verify <file|-> [labels...]");
+    }
 
-    let t_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&[
-            "***", //
-            " * ", //
-            " * ", //
-        ]),
-        attrs: geometry::ShapeAttrs {
-            chiral: false,
-            rotations: 3,
-        },
-    });
+    let board = read_board(puzzle, &args[0]);
+
+    let blocked: Vec<Point> = if args.len() > 1 {
+        args[1..]
+            .iter()
+            .map(|label| {
+                puzzle
+                    .find(label)
+                    .unwrap_or_else(|| exit_with(format!("Label '{}' not found.", label)))
+            })
+            .collect()
+    } else {
+        verify::uncovered(&board)
+    };
+
+    let mut problems = verify::verify(&board, &puzzle.shapes, &blocked);
+    if args.len() == 1 {
+        // without labels the uncovered cells are taken as the date
+        problems.extend(verify::verify_date(puzzle, &blocked));
+    }
+    if problems.is_empty() {
+        let labels: Vec<&str> = puzzle
+            .cells
+            .iter()
+            .filter(|lp| blocked.contains(&lp.point))
+            .map(|lp| lp.label.as_str())
+            .collect();
+        println!("Valid solution for {}.", labels.join(" "));
+    } else {
+        for problem in problems {
+            println!("{}", problem);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn exit_with(e: impl std::fmt::Display) -> ! {
+    eprintln!("{}", e);
+    std::process::exit(1);
+}
+
+fn run_build_db(puzzle: &puzzle::Puzzle, args: &[String]) {
+    if args.is_empty() {
+        exit_with("Usage: build-db <file> [labels...]");
+    }
+
+    // only build the dates that contain all of the given labels
+    let dates: Vec<Vec<String>> = puzzle
+        .dates()
+        .into_iter()
+        .filter(|date| args[1..].iter().all(|label| date.contains(label)))
+        .collect();
+
+    let table = puzzle.placement_table();
+    let mut entries = Vec::with_capacity(dates.len());
+    for (i, date) in dates.iter().enumerate() {
+        let board = puzzle.make_board(date).unwrap_or_else(|e| exit_with(e));
+        let solvers = create_parallel_solver(board, puzzle.shapes.clone(), 2, &table);
 
-    let v_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&[
-            "***", //
-            "*",   //
-            "*",   //
-        ]),
-        attrs: geometry::ShapeAttrs {
-            chiral: false,
-            rotations: 3,
+        let mut solutions = vec![];
+        run_parallel(solvers, num_cpus::get(), |solution| {
+            if let Ok(encoded) = encoding::encode(&solution, &puzzle.shapes) {
+                solutions.push(encoded);
+            }
+            true
+        });
+
+        eprintln!(
+            "[{}/{}] {}: {} solutions",
+            i + 1,
+            dates.len(),
+            date.join(" "),
+            solutions.len()
+        );
+        entries.push((date.clone(), solutions));
+    }
+
+    database::write(&args[0], puzzle.checksum(), puzzle.shapes.len(), &entries)
+        .unwrap_or_else(|e| exit_with(e));
+}
+
+// Rates every date containing all of the given labels and lists them from
+// hardest to easiest
+fn run_difficulty(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let dates: Vec<Vec<String>> = puzzle
+        .dates()
+        .into_iter()
+        .filter(|date| args.iter().all(|label| date.contains(label)))
+        .collect();
+    if dates.is_empty() {
+        exit_with(format!("No date contains '{}'.", args.join(" ")));
+    }
+
+    let total = dates.len();
+    let shared = puzzle.clone();
+    let table = puzzle.placement_table();
+    let mut ratings = Vec::with_capacity(total);
+    run_jobs(
+        dates,
+        num_cpus::get(),
+        move |date: Vec<String>| {
+            let board = shared.make_board(&date).unwrap();
+            let rating = difficulty::rate(board, shared.shapes.clone(), Arc::clone(&table));
+            (date, rating)
+        },
+        |(date, rating)| {
+            eprintln!(
+                "[{}/{}] {}: {} solutions",
+                ratings.len() + 1,
+                total,
+                date.join(" "),
+                rating.solutions
+            );
+            ratings.push((date, rating));
         },
+    );
+
+    ratings.sort_by(|(_, a), (_, b)| b.score.total_cmp(&a.score));
+    println!("date\tscore\tsolutions\tnodes\tfirst forced");
+    for (date, rating) in ratings {
+        let forced = rating
+            .first_forced
+            .map_or("-".to_string(), |depth| depth.to_string());
+        println!(
+            "{}\t{:.2}\t{}\t{}\t{}",
+            date.join(" "),
+            rating.score,
+            rating.solutions,
+            rating.nodes,
+            forced
+        );
+    }
+}
+
+fn run_explain(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let board = puzzle.make_board(args).unwrap_or_else(|e| exit_with(e));
+
+    match explain::explain(&puzzle.placement_table(), &board, &puzzle.shapes) {
+        Some(reason) => println!("{}", reason),
+        None => println!("Solvable."),
+    }
+}
+
+fn run_export_cnf(puzzle: &puzzle::Puzzle, args: &[String]) {
+    if args.len() < 2 {
+        exit_with("Usage: export-cnf <cnf file> <mapping file> [labels...]");
+    }
+
+    let labels = &args[2..];
+    let board = puzzle.make_board(labels).unwrap_or_else(|e| exit_with(e));
+    let (cnf, placements) = sat::to_cnf(&puzzle.placement_table(), &board);
+
+    std::fs::write(&args[0], sat::write_dimacs(&cnf)).unwrap_or_else(|e| exit_with(e));
+    std::fs::write(&args[1], sat::write_mapping(labels, &placements))
+        .unwrap_or_else(|e| exit_with(e));
+    eprintln!("{} variables, {} clauses.", cnf.vars, cnf.clauses.len());
+}
+
+// Writes a MiniZinc model or SMT-LIB script with the same mapping file as
+// export-cnf, so import-model reads back the solver's answer
+fn run_export_model(puzzle: &puzzle::Puzzle, args: &[String], smt: bool) {
+    if args.len() < 2 {
+        let command = if smt { "export-smt" } else { "export-mzn" };
+        exit_with(format!(
+            "Usage: {} <model file> <mapping file> [labels...]",
+            command
+        ));
+    }
+
+    let labels = &args[2..];
+    let board = puzzle.make_board(labels).unwrap_or_else(|e| exit_with(e));
+    let (model, placements) = if smt {
+        constraint::to_smtlib(&puzzle.placement_table(), &board)
+    } else {
+        constraint::to_minizinc(&puzzle.placement_table(), &board)
+    };
+
+    std::fs::write(&args[0], model).unwrap_or_else(|e| exit_with(e));
+    std::fs::write(&args[1], sat::write_mapping(labels, &placements))
+        .unwrap_or_else(|e| exit_with(e));
+    eprintln!("{} placement variables.", placements.len());
+}
+
+// Labels after --secondary stay open but may be left uncovered, e.g. all the
+// weekdays to cover a month and day on any weekday
+fn run_export_matrix(puzzle: &puzzle::Puzzle, args: &[String]) {
+    if args.is_empty() {
+        exit_with("Usage: export-matrix <file> [labels...] [--secondary labels...]");
+    }
+
+    let split = args.iter().position(|arg| arg == "--secondary");
+    let (labels, secondary) = match split {
+        Some(i) => (&args[1..i], &args[i + 1..]),
+        None => (&args[1..], &args[..0]),
+    };
+    let board = puzzle.make_board(labels).unwrap_or_else(|e| exit_with(e));
+    let secondary: Vec<Point> = secondary
+        .iter()
+        .map(|label| match puzzle.find(label) {
+            Some(p) if board.at(&p).is_none() => p,
+            Some(_) => exit_with(format!("Label '{}' is blocked.", label)),
+            None => exit_with(format!("Label '{}' not found.", label)),
+        })
+        .collect();
+
+    let text = matrix::to_matrix(&puzzle.placement_table(), &board, &secondary);
+    std::fs::write(&args[0], text).unwrap_or_else(|e| exit_with(e));
+}
+
+// Counts solutions without listing them. The search keeps a transposition
+// table, 64 MB unless --memo gives another size or 0 to go without, shared
+// by all dates so sub-boards they have in common are counted once. --all
+// counts every date containing the labels; --verify checks each count by
+// enumerating
+fn run_count(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let mut megabytes = memo::DEFAULT_MEGABYTES;
+    let mut all = false;
+    let mut verify = false;
+    let mut labels = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--memo" && i + 1 < args.len() {
+            megabytes = args[i + 1].parse::<usize>().unwrap_or_else(|_| {
+                exit_with(format!("Invalid value for --memo: {}", args[i + 1]))
+            });
+            i += 1;
+        } else if args[i] == "--all" {
+            all = true;
+        } else if args[i] == "--verify" {
+            verify = true;
+        } else {
+            labels.push(args[i].clone());
+        }
+        i += 1;
+    }
+
+    let dates: Vec<Vec<String>> = if all {
+        puzzle
+            .dates()
+            .into_iter()
+            .filter(|date| labels.iter().all(|label| date.contains(label)))
+            .collect()
+    } else {
+        vec![labels]
+    };
+
+    let started = std::time::Instant::now();
+    let table = puzzle.placement_table();
+    let mut memo = (megabytes > 0).then(|| memo::create_memo(megabytes));
+    let mut nodes = 0;
+    for date in &dates {
+        let board = puzzle.make_board(date).unwrap_or_else(|e| exit_with(e));
+        let (count, tried) = memo::count_solutions(&table, &board, &puzzle.shapes, memo.as_mut())
+            .unwrap_or_else(|e| exit_with(e));
+        nodes += tried;
+
+        if verify {
+            let mut s =
+                solver::create_solver_with_table(board, puzzle.shapes.clone(), Arc::clone(&table));
+            let mut listed = 0;
+            while solver::step(&mut s, |e, _| {
+                if let solver::StepEvent::Solved = e {
+                    listed += 1;
+                }
+            }) {}
+            if listed != count {
+                exit_with(format!(
+                    "{}: counted {} solutions but enumeration found {}.",
+                    date.join(" "),
+                    count,
+                    listed
+                ));
+            }
+        }
+
+        if all {
+            println!("{}\t{}", date.join(" "), count);
+        } else {
+            println!("{} solutions", count);
+        }
+    }
+
+    eprintln!(
+        "{} placements tried in {:.2}s{}.",
+        nodes,
+        started.elapsed().as_secs_f64(),
+        if verify { ", counts verified" } else { "" }
+    );
+    if let Some(memo) = memo {
+        eprintln!(
+            "Memo of {:.1} MB: {} lookups, {} hits ({:.1}%), {} stored, {} replaced.",
+            memo.bytes() as f64 / (1 << 20) as f64,
+            memo.lookups,
+            memo.hits,
+            100.0 * memo.hit_rate(),
+            memo.stores,
+            memo.replaced
+        );
+    }
+}
+
+fn run_import_model(puzzle: &puzzle::Puzzle, args: &[String]) {
+    if args.len() != 2 {
+        exit_with("Usage: import-model <mapping file> <model file|->");
+    }
+
+    let (labels, placements) =
+        sat::parse_mapping(&read_input(&args[0])).unwrap_or_else(|e| exit_with(e));
+    let board = puzzle.make_board(&labels).unwrap_or_else(|e| exit_with(e));
+
+    match constraint::parse_assignment(&read_input(&args[1])).unwrap_or_else(|e| exit_with(e)) {
+        Some(trues) => {
+            let solved = sat::model_to_board(&board, &puzzle.shapes, &placements, &trues)
+                .unwrap_or_else(|e| exit_with(e));
+            print_board(&solved);
+        }
+        None => println!("No solution exists."),
+    }
+}
+
+// Solves a date through the CNF export with the built-in DPLL solver, or
+// with --cdcl the clause learning one, which can also list -m solutions (0
+// for all). Prints boards or, with --model, models as a SAT solver would
+fn run_sat(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let mut model = false;
+    let mut use_cdcl = false;
+    let mut goal = 1;
+    let mut labels = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--model" {
+            model = true;
+        } else if args[i] == "--cdcl" {
+            use_cdcl = true;
+        } else if args[i] == "-m" && i + 1 < args.len() {
+            goal = args[i + 1]
+                .parse::<usize>()
+                .unwrap_or_else(|_| exit_with(format!("Invalid value for -m: {}", args[i + 1])));
+            i += 1;
+        } else {
+            labels.push(args[i].clone());
+        }
+        i += 1;
+    }
+    if goal != 1 && !use_cdcl {
+        exit_with("Listing several solutions needs --cdcl.");
+    }
+
+    let started = std::time::Instant::now();
+    let board = puzzle.make_board(&labels).unwrap_or_else(|e| exit_with(e));
+    let (cnf, placements) = sat::to_cnf(&puzzle.placement_table(), &board);
+
+    let print = |trues: &[usize]| {
+        if model {
+            let lits: Vec<String> = (1..=cnf.vars)
+                .map(|var| {
+                    let sign = if trues.contains(&var) { "" } else { "-" };
+                    format!("{}{}", sign, var)
+                })
+                .collect();
+            println!("s SATISFIABLE\nv {} 0", lits.join(" "));
+        } else {
+            let solved = sat::model_to_board(&board, &puzzle.shapes, &placements, trues)
+                .unwrap_or_else(|e| exit_with(e));
+            println!("Solved!");
+            print_board(&solved);
+        }
+    };
+
+    let mut count = 0;
+    if use_cdcl {
+        let mut engine = cdcl::create_cdcl(&cnf);
+        while goal == 0 || count < goal {
+            match cdcl::solve(&mut engine) {
+                Some(trues) => {
+                    print(&trues);
+                    count += 1;
+                    cdcl::block(&mut engine, &trues);
+                }
+                None => break,
+            }
+        }
+        eprintln!(
+            "{} solutions in {:.2}s ({} conflicts, {} decisions).",
+            count,
+            started.elapsed().as_secs_f64(),
+            engine.conflicts,
+            engine.decisions
+        );
+    } else if let Some(trues) = sat::dpll(&cnf) {
+        print(&trues);
+        count += 1;
+    }
+
+    if count == 0 {
+        if model {
+            println!("s UNSATISFIABLE");
+        } else {
+            println!("No solution exists.");
+        }
+    }
+}
+
+fn run_query(puzzle: &puzzle::Puzzle, args: &[String]) {
+    if args.len() < 2 {
+        exit_with("Usage: query <file> counts|count|list|random [labels...] [--seed N]");
+    }
+
+    let mut db = database::Database::open(&args[0]).unwrap_or_else(|e| exit_with(e));
+    if db.checksum != puzzle.checksum() {
+        exit_with("Database was built for a different puzzle.");
+    }
+
+    let mut labels = vec![];
+    let mut seed = None;
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--seed" && i + 1 < args.len() {
+            seed = Some(args[i + 1].parse::<u64>().unwrap_or_else(|_| {
+                exit_with(format!("Invalid value for --seed: {}", args[i + 1]))
+            }));
+            i += 1;
+        } else {
+            labels.push(args[i].clone());
+        }
+        i += 1;
+    }
+
+    if args[1] == "counts" {
+        for date in &db.dates {
+            println!("{}: {}", date.labels.join(" "), date.count);
+        }
+        return;
+    }
+
+    let date = db
+        .find(&labels)
+        .unwrap_or_else(|| exit_with(format!("Date '{}' not in database.", labels.join(" "))));
+
+    let solutions = match args[1].as_str() {
+        "count" => {
+            println!("{}", db.dates[date].count);
+            return;
+        }
+        "list" => db.solutions(date),
+        "random" => {
+            let mut rng = seed.map(rng::Rng::new).unwrap_or_else(rng::Rng::from_time);
+            db.sample(date, 1, &mut rng)
+        }
+        other => exit_with(format!("Unknown query '{}'.", other)),
+    }
+    .unwrap_or_else(|e| exit_with(e));
+
+    for solution in solutions {
+        let board = encoding::decode(&solution, &puzzle.points(), &puzzle.shapes)
+            .unwrap_or_else(|e| exit_with(e));
+        print_board(&board);
+        println!("{}", solution);
+    }
+}
+
+fn print_hex_board(board: &board::Board<Hex>) {
+    print_with(board, |ps, to_char| convert_hex_to_strings(ps, to_char));
+}
+
+fn print_cube_board(board: &board::Board<Cube>) {
+    print_with(board, |ps, to_char| convert_cube_to_strings(ps, to_char));
+}
+
+fn print_tri_board(board: &board::Board<Tri>) {
+    print_with(board, |ps, to_char| convert_tri_to_strings(ps, to_char));
+}
+
+fn print_wrapped_board(board: &board::Board<Wrapped>) {
+    print_with(board, |ps, to_char| {
+        convert_wrapped_to_strings(ps, board.topology(), to_char)
     });
+}
 
-    let z_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&[
-            "**",  //
-            " *",  //
-            " **", //
-        ]),
-        attrs: geometry::ShapeAttrs {
-            chiral: true,
-            rotations: 1,
-        },
+fn hex_svg(board: &board::Board<Hex>) -> String {
+    svg::hex_to_svg(&board.all, |p| board.at(p))
+}
+
+fn tri_svg(board: &board::Board<Tri>) -> String {
+    svg::tri_to_svg(&board.all, |p| board.at(p))
+}
+
+// Solves one of the sample puzzles on other grids, printing solutions with
+// `print` and writing the first one as SVG if asked to
+fn run_sample<T: topology::Topology>(
+    board: board::Board<T>,
+    shapes: Vec<(String, geometry::Shape<T::Point>)>,
+    args: &[String],
+    print: fn(&board::Board<T>),
+    to_svg: Option<fn(&board::Board<T>) -> String>,
+) {
+    let mut goal = 1;
+    let mut svg_path = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "-m" && i + 1 < args.len() {
+            goal = args[i + 1]
+                .parse::<usize>()
+                .unwrap_or_else(|_| exit_with(format!("Invalid value for -m: {}", args[i + 1])));
+            i += 1;
+        } else if args[i] == "--svg" && i + 1 < args.len() && to_svg.is_some() {
+            svg_path = Some(args[i + 1].clone());
+            i += 1;
+        } else {
+            exit_with(format!("Unknown argument '{}'.", args[i]));
+        }
+        i += 1;
+    }
+
+    let mut s = create_solver(board, shapes);
+    let mut count = 0;
+
+    while count < goal
+        && solver::step(&mut s, |e, b: &board::Board<T>| {
+            if let solver::StepEvent::Solved = e {
+                println!("Solved!");
+                print(b);
+
+                if let (0, Some(path), Some(to_svg)) = (count, &svg_path, to_svg) {
+                    std::fs::write(path, to_svg(b)).unwrap_or_else(|e| exit_with(e));
+                }
+                count += 1;
+            }
+        })
+    {}
+
+    if count == 0 {
+        println!("No solution found.");
+    }
+}
+
+// Lists every piece of `n` cells on the given grid, or only how many there are
+fn run_enumerate(args: &[String]) {
+    let mut n = None;
+    let mut symmetry = enumerate::Symmetry::Free;
+    let mut grid = "square";
+    let mut count_only = false;
+    for arg in args {
+        match arg.as_str() {
+            "--fixed" => symmetry = enumerate::Symmetry::Fixed,
+            "--one-sided" => symmetry = enumerate::Symmetry::OneSided,
+            "--free" => symmetry = enumerate::Symmetry::Free,
+            "--hex" | "--tri" => grid = &arg[2..],
+            "-c" => count_only = true,
+            _ => {
+                n = Some(
+                    arg.parse::<usize>()
+                        .unwrap_or_else(|_| exit_with(format!("Unknown argument '{}'.", arg))),
+                )
+            }
+        }
+    }
+    let n = n.unwrap_or_else(|| {
+        exit_with("Usage: enumerate <n> [--free|--one-sided|--fixed] [--hex|--tri] [-c]")
     });
 
-    let s_piece = convert_to_shape(&VisualShape {
-        points: to_strings(&[
-            "**",  //
-            " **", //
-        ]),
-        attrs: geometry::ShapeAttrs {
-            chiral: true,
-            rotations: 1,
+    match grid {
+        "hex" => print_polyforms(
+            enumerate::polyforms(&Hex, geometry::HexPoint { q: 0, r: 0 }, n, symmetry),
+            count_only,
+            |ps| convert_hex_to_strings(ps, |_| '*'),
+        ),
+        "tri" => print_polyforms(
+            enumerate::polyforms(&Tri, geometry::TriPoint::new(0, 0, true), n, symmetry),
+            count_only,
+            |ps| convert_tri_to_strings(ps, |_| '*'),
+        ),
+        _ => print_polyforms(
+            enumerate::polyforms(&Square, Point { x: 0, y: 0 }, n, symmetry),
+            count_only,
+            |ps| convert_to_strings(ps, |_| '*'),
+        ),
+    }
+}
+
+fn print_polyforms<P: Clone>(
+    shapes: Vec<geometry::Shape<P>>,
+    count_only: bool,
+    draw: fn(&[P]) -> Vec<String>,
+) {
+    if !count_only {
+        for (i, shape) in shapes.iter().enumerate() {
+            let chiral = if shape.attrs.chiral { ", chiral" } else { "" };
+            println!("{}: {} rotations{}", i + 1, shape.attrs.rotations, chiral);
+            println!("{}\n", draw(&shape.points).join("\n"));
+        }
+    }
+
+    println!("{}", shapes.len());
+}
+
+// Search statistics for --stats, on stderr so the boards can still be piped
+fn print_search_stats(
+    piece_order: order::PieceOrder,
+    shapes: &[(String, geometry::Shape<Point>)],
+    placement_counts: &[usize],
+    nodes: Option<usize>,
+    solutions: usize,
+    elapsed: std::time::Duration,
+) {
+    let pieces: Vec<String> = order::labels(shapes)
+        .iter()
+        .zip(placement_counts)
+        .map(|(label, count)| format!("{}({})", label, count))
+        .collect();
+    let name = format!("{:?}", piece_order).to_lowercase();
+    eprintln!("Piece order ({}): {}", name, pieces.join(" "));
+    if let Some(nodes) = nodes {
+        eprintln!("Placements tried: {}", nodes);
+    }
+    eprintln!("Solutions: {}", solutions);
+    eprintln!("Time: {:.2}s", elapsed.as_secs_f64());
+}
+
+fn print_progress(progress: design::Progress, what: &str) {
+    let total = if progress.total > 0 {
+        format!("/{}", progress.total)
+    } else {
+        String::new()
+    };
+    eprint!(
+        "\r[{}{}] date {}, {} solvable {}, best has {} solutions on its hardest date ",
+        progress.sets + 1,
+        total,
+        progress.dates,
+        progress.found,
+        what,
+        progress.best
+    );
+}
+
+// Searches for piece sets out of all free polyominoes of the given sizes
+// that solve the calendar on every date
+fn run_design(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let mut sizes = vec![];
+    let mut limits = design::Limits {
+        cap: 10,
+        budget: None,
+        sets: None,
+    };
+    let mut top = 10;
+    let mut i = 0;
+    while i + 1 < args.len() {
+        let value = &args[i + 1];
+        let number = || {
+            value
+                .parse::<usize>()
+                .unwrap_or_else(|_| exit_with(format!("Invalid value for {}: {}", args[i], value)))
+        };
+        match args[i].as_str() {
+            "--sizes" => {
+                sizes = value
+                    .split(',')
+                    .map(|size| {
+                        size.parse::<usize>()
+                            .unwrap_or_else(|_| exit_with(format!("Invalid size: {}", size)))
+                    })
+                    .collect()
+            }
+            "--cap" => limits.cap = number(),
+            "--budget" => limits.budget = Some(number()),
+            "--limit" => limits.sets = Some(number()),
+            "--top" => top = number(),
+            other => exit_with(format!("Unknown argument '{}'.", other)),
+        }
+        i += 2;
+    }
+    if i < args.len() {
+        exit_with("Usage: design [--sizes N[,N...]] [--cap N] [--budget N] [--limit N] [--top N]");
+    }
+
+    // without sizes the puzzle's own pieces are checked; enumerated ones get
+    // one character each so they can be told apart on the board
+    let names: Vec<char> = ('A'..='Z').chain('a'..='z').chain('0'..='9').collect();
+    let pool: Vec<(String, geometry::Shape<Point>)> = if sizes.is_empty() {
+        puzzle.shapes.clone()
+    } else {
+        let shapes: Vec<geometry::Shape<Point>> = sizes
+            .iter()
+            .flat_map(|&n| {
+                enumerate::polyforms(&Square, Point { x: 0, y: 0 }, n, enumerate::Symmetry::Free)
+            })
+            .collect();
+        if shapes.len() > names.len() {
+            exit_with(format!(
+                "Pool of {} pieces is more than the {} labels available.",
+                shapes.len(),
+                names.len()
+            ));
+        }
+        shapes
+            .into_iter()
+            .zip(names)
+            .map(|(shape, name)| (name.to_string(), shape))
+            .collect()
+    };
+    eprintln!("Pool of {} pieces.", pool.len());
+
+    let candidates = design::design(puzzle, &pool, &limits, |p| print_progress(p, "sets"));
+    eprintln!();
+
+    for candidate in candidates.iter().take(top) {
+        let pieces: Vec<&(String, geometry::Shape<Point>)> =
+            candidate.pieces.iter().map(|&i| &pool[i]).collect();
+        let labels: Vec<&str> = pieces.iter().map(|(label, _)| label.as_str()).collect();
+        println!(
+            "{}: at least {} solutions per date",
+            labels.join(""),
+            candidate.min_count
+        );
+
+        // draw the pieces side by side
+        let drawings: Vec<Vec<String>> = pieces
+            .iter()
+            .map(|(label, shape)| {
+                let c = label.chars().next().unwrap();
+                convert_to_strings(&shape.points, |_| c)
+            })
+            .collect();
+        let height = drawings.iter().map(|d| d.len()).max().unwrap_or(0);
+        for row in 0..height {
+            let line: Vec<String> = drawings
+                .iter()
+                .map(|d| {
+                    let width = d[0].len();
+                    format!("{:width$}", d.get(row).map_or("", |s| s.as_str()))
+                })
+                .collect();
+            println!("{}", line.join("  ").trim_end());
+        }
+        println!();
+    }
+}
+
+// Looks for other arrangements of the puzzle's cells within a box, printing
+// every layout found that is solvable on every date
+fn run_layout(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let (_, max) = stringify::bounds(&puzzle.points());
+    let (mut width, mut height) = (max.x + 1, max.y + 1);
+    let mut limits = design::Limits {
+        cap: 1,
+        budget: None,
+        sets: None,
+    };
+    let mut found = None;
+    let mut seed = None;
+    let mut i = 0;
+    while i + 1 < args.len() {
+        let value = &args[i + 1];
+        let number = || {
+            value
+                .parse::<usize>()
+                .unwrap_or_else(|_| exit_with(format!("Invalid value for {}: {}", args[i], value)))
+        };
+        match args[i].as_str() {
+            "--size" => {
+                let size = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse::<i16>().ok()?, h.parse::<i16>().ok()?)));
+                (width, height) =
+                    size.unwrap_or_else(|| exit_with(format!("Invalid size: {}", value)));
+            }
+            "--cap" => limits.cap = number(),
+            "--budget" => limits.budget = Some(number()),
+            "--limit" => limits.sets = Some(number()),
+            "--found" => found = Some(number()),
+            "--seed" => seed = Some(number() as u64),
+            other => exit_with(format!("Unknown argument '{}'.", other)),
+        }
+        i += 2;
+    }
+    if i < args.len() {
+        exit_with(
+            "Usage: layout [--size WxH] [--cap N] [--budget N] [--limit N] [--found N] [--seed N]",
+        );
+    }
+    if width <= max.x || height <= max.y {
+        exit_with(format!(
+            "The box must be at least {}x{} to hold the board.",
+            max.x + 1,
+            max.y + 1
+        ));
+    }
+
+    let mut rng = seed.map(rng::Rng::new).unwrap_or_else(rng::Rng::from_time);
+    let mut count = 0;
+    design::design_layouts(
+        puzzle,
+        width,
+        height,
+        &limits,
+        &mut rng,
+        |p| print_progress(p, "layouts"),
+        |layout, min_count| {
+            eprintln!();
+            println!("# at least {} solutions per date", min_count);
+            println!("{}\n", puzzle::format_puzzle(layout));
+            count += 1;
+            found.is_none_or(|found| count < found)
         },
-    });
+    );
+    eprintln!();
+}
+
+// Solves the calendar with its edges joined: "x" wraps the columns into a
+// cylinder, "y" the rows, and "xy" both into a torus
+fn run_wrap(puzzle: &puzzle::Puzzle, args: &[String]) {
+    let (wrap_x, wrap_y) = match args.first().map(|s| s.as_str()) {
+        Some("x") => (true, false),
+        Some("y") => (false, true),
+        Some("xy") => (true, true),
+        _ => exit_with("Usage: wrap <x|y|xy> [labels...] [-m N]"),
+    };
+
+    let mut board = make_wrapped_board(puzzle.points(), wrap_x, wrap_y);
+    let mut options = vec![];
+    let mut i = 1;
+    while i < args.len() {
+        if args[i].starts_with('-') {
+            options.extend(args[i..(i + 2).min(args.len())].iter().cloned());
+            i += 1;
+        } else {
+            let p = puzzle
+                .find(&args[i])
+                .unwrap_or_else(|| exit_with(format!("Label '{}' not found.", args[i])));
+            board.fill(&vec![p], Point { x: 0, y: 0 }, "*");
+        }
+        i += 1;
+    }
+
+    run_sample(
+        board,
+        puzzle.shapes.clone(),
+        &options,
+        print_wrapped_board,
+        None,
+    );
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+
+    // a puzzle file given first replaces the built-in calendar
+    let puzzle = if args.get(1).map(|s| s.as_str()) == Some("--puzzle") && args.len() > 2 {
+        let text = read_input(&args[2]);
+        args.drain(1..3);
+        puzzle::parse_puzzle(&text).unwrap_or_else(|e| exit_with(e))
+    } else {
+        puzzle::calendar()
+    };
+    let board_pts = &puzzle.cells;
+
+    let points: Vec<Point> = puzzle.points();
+    let mut board = make_point_board(points);
 
     let origin = Point { x: 0, y: 0 };
     let mut count = 0;
     let mut verbose = false;
     let mut parallel = false;
-    let args: Vec<String> = std::env::args().collect();
+    let mut encode = false;
+    let mut random = false;
+    let mut seed = None;
+    let mut db_path = None;
+    let mut piece_order = order::PieceOrder::Given;
+    let mut stats = false;
+    let mut unique = false;
+    let command = args.get(1).map(|s| s.as_str()).unwrap_or("");
+    match command {
+        "hint" => return run_hint(&puzzle, &args[2..]),
+        "encode" => return run_encode(&puzzle, &args[2..]),
+        "decode" => return run_decode(&puzzle, &args[2..]),
+        "verify" => return run_verify(&puzzle, &args[2..]),
+        "build-db" => return run_build_db(&puzzle, &args[2..]),
+        "query" => return run_query(&puzzle, &args[2..]),
+        "difficulty" => return run_difficulty(&puzzle, &args[2..]),
+        "explain" => return run_explain(&puzzle, &args[2..]),
+        "export-cnf" => return run_export_cnf(&puzzle, &args[2..]),
+        "export-mzn" => return run_export_model(&puzzle, &args[2..], false),
+        "export-smt" => return run_export_model(&puzzle, &args[2..], true),
+        "count" => return run_count(&puzzle, &args[2..]),
+        "export-matrix" => return run_export_matrix(&puzzle, &args[2..]),
+        "import-model" => return run_import_model(&puzzle, &args[2..]),
+        "sat" => return run_sat(&puzzle, &args[2..]),
+        "hex" => {
+            let puzzle = puzzle::polyhex();
+            return run_sample(
+                board::Board::new(puzzle.points(), Hex),
+                puzzle.shapes,
+                &args[2..],
+                print_hex_board,
+                Some(hex_svg),
+            );
+        }
+        "tri" => {
+            let puzzle = puzzle::polyiamond();
+            return run_sample(
+                board::Board::new(puzzle.points(), Tri),
+                puzzle.shapes,
+                &args[2..],
+                print_tri_board,
+                Some(tri_svg),
+            );
+        }
+        "cube" => {
+            // an optional puzzle file comes before the options
+            let (puzzle, rest) = match args.get(2).filter(|arg| !arg.starts_with('-')) {
+                Some(path) => {
+                    let text = read_input(path);
+                    let puzzle = puzzle::cube_puzzle(&text).unwrap_or_else(|e| exit_with(e));
+                    (puzzle, &args[3..])
+                }
+                None => (puzzle::soma(), &args[2..]),
+            };
+            let board = board::Board::new(puzzle.points(), Cube);
+            return run_sample(board, puzzle.shapes, rest, print_cube_board, None);
+        }
+        "wrap" => return run_wrap(&puzzle, &args[2..]),
+        "enumerate" => return run_enumerate(&args[2..]),
+        "design" => return run_design(&puzzle, &args[2..]),
+        "layout" => return run_layout(&puzzle, &args[2..]),
+        "print-puzzle" => return println!("{}", puzzle::format_puzzle(&puzzle)),
+        _ => (),
+    }
+
     let mut goal = 1;
     let mut i = 1;
     while i < args.len() {
@@ -173,14 +1128,43 @@ fn main() {
             if let Ok(val) = args[i + 1].parse::<usize>() {
                 goal = val;
             } else {
-                eprintln!("Invalid value for -m: {}", args[i + 1]);
-                std::process::exit(1);
+                exit_with(format!("Invalid value for -m: {}", args[i + 1]));
             }
             i += 1;
         } else if args[i] == "-v" {
             verbose = true;
         } else if args[i] == "-p" {
             parallel = true;
+        } else if args[i] == "-e" {
+            encode = true;
+        } else if args[i] == "--random" {
+            random = true;
+        } else if args[i] == "--seed" && i + 1 < args.len() {
+            if let Ok(val) = args[i + 1].parse::<u64>() {
+                seed = Some(val);
+            } else {
+                exit_with(format!("Invalid value for --seed: {}", args[i + 1]));
+            }
+            i += 1;
+        } else if args[i] == "--db" && i + 1 < args.len() {
+            db_path = Some(args[i + 1].clone());
+            i += 1;
+        } else if args[i] == "--order" && i + 1 < args.len() {
+            piece_order = match args[i + 1].as_str() {
+                "given" => order::PieceOrder::Given,
+                "fewest" => order::PieceOrder::Fewest,
+                "largest" => order::PieceOrder::Largest,
+                "pilot" => order::PieceOrder::Pilot,
+                other => exit_with(format!(
+                    "Invalid value for --order: {} (given, fewest, largest or pilot)",
+                    other
+                )),
+            };
+            i += 1;
+        } else if args[i] == "--stats" {
+            stats = true;
+        } else if args[i] == "--unique-up-to-symmetry" {
+            unique = true;
         } else {
             // Try to match argument to a labeled point and fill it in the board
             let label = &args[i];
@@ -190,106 +1174,141 @@ fn main() {
 
                 if verbose {
                     println!("Filled label '{}' at point {:?}", label, ps);
-                    print_board(&board.all, &board);
+                    print_board(&board);
                     println!();
                 }
             } else {
-                eprintln!("Label '{}' not found.", label);
-                std::process::exit(1);
+                exit_with(format!("Label '{}' not found.", label));
             }
         }
         i += 1;
     }
-    let shapes = vec![
-        ("Z".to_string(), z_piece),
-        ("V".to_string(), v_piece),
-        ("U".to_string(), u_piece),
-        ("T".to_string(), t_piece),
-        ("P".to_string(), p_piece),
-        ("N".to_string(), n_piece),
-        ("L".to_string(), l_piece),
-        ("J".to_string(), j_piece),
-        ("I".to_string(), i_piece),
-        ("S".to_string(), s_piece),
-    ];
-
-    if parallel {
-        let solvers = create_parallel_solver(board, shapes, 2);
-        println!("Created {} parallel solvers.", solvers.len());
+    let started = std::time::Instant::now();
+    let table = puzzle.placement_table();
+    let shapes = order::order_pieces(&table, &board, &puzzle.shapes, piece_order);
+    let placement_counts = order::placement_counts(&table, &board, &shapes);
+    let print_stats = |nodes: Option<usize>, solutions: usize| {
+        if stats {
+            print_search_stats(
+                piece_order,
+                &shapes,
+                &placement_counts,
+                nodes,
+                solutions,
+                started.elapsed(),
+            );
+        }
+    };
+    let mut rng = seed.map(rng::Rng::new).unwrap_or_else(rng::Rng::from_time);
 
-        let (solver_tx, solver_rx) = mpsc::channel();
-        let (solution_tx, solution_rx) = mpsc::channel();
+    // with --unique-up-to-symmetry only the first of each set of solutions
+    // related by a symmetry of the open cells is printed and counted
+    let open: Vec<Point> = board.remaining().into_iter().cloned().collect();
+    let symmetries = symmetry::symmetries(&Square, &open, &table);
+    let keep =
+        |b: &board::Board<Square>| !unique || symmetry::is_canonical(b, &open, &symmetries, &table);
+    let mut found = 0;
+    let print_unique = |found: usize, count: usize| {
+        if unique {
+            println!(
+                "{} solutions, {} unique up to symmetry (the open cells have a symmetry group of order {}).",
+                found,
+                count,
+                symmetries.len()
+            );
+        }
+    };
 
-        let solver_rx = Arc::new(Mutex::new(solver_rx));
+    if let (true, Some(path)) = (random, &db_path) {
+        // exactly uniform, as all solutions of the date are known
+        let mut db = database::Database::open(path).unwrap_or_else(|e| exit_with(e));
+        if db.checksum != puzzle.checksum() {
+            exit_with("Database was built for a different puzzle.");
+        }
 
-        let num_workers = num_cpus::get();
-        println!("Spawning {} worker threads.", num_workers);
+        let blocked: Vec<String> = puzzle
+            .cells
+            .iter()
+            .filter(|lp| board.at(&lp.point).is_some())
+            .map(|lp| lp.label.clone())
+            .collect();
+        let date = db
+            .find(&blocked)
+            .unwrap_or_else(|| exit_with(format!("Date '{}' not in database.", blocked.join(" "))));
 
-        // Spawn worker threads
-        for _ in 0..num_workers {
-            let solver_rx = Arc::clone(&solver_rx);
-            let solution_tx = solution_tx.clone();
-            thread::spawn(move || {
-                loop {
-                    // Lock and receive a solver
-                    let maybe_solver = {
-                        let lock = solver_rx.lock().unwrap();
-                        lock.recv()
-                    };
-                    match maybe_solver {
-                        Ok(mut solver) => {
-                            let mut handle_step_event =
-                                |e: solver::StepEvent, b: &board::Board<Point>| {
-                                    if let solver::StepEvent::Solved = e {
-                                        let _ = solution_tx.send(b.clone());
-                                    }
-                                };
-                            while solver::step(&mut solver, &mut handle_step_event) {}
-                        }
-                        Err(_) => break, // Channel closed, exit thread
-                    }
-                }
-            });
+        let solutions = db
+            .sample(date, goal, &mut rng)
+            .unwrap_or_else(|e| exit_with(e));
+        for solution in solutions {
+            let b = encoding::decode(&solution, &puzzle.points(), &puzzle.shapes)
+                .unwrap_or_else(|e| exit_with(e));
+            println!("Solved!");
+            print_solution(&b, &puzzle.shapes, encode);
         }
+    } else if parallel {
+        let mut solvers = create_parallel_solver(board, shapes.clone(), 2, &table);
+        println!("Created {} parallel solvers.", solvers.len());
 
-        drop(solution_tx); // close channel so main thread can exit when done
-
-        // Send solvers to workers
-        for solver in solvers {
-            solver_tx.send(solver).unwrap();
+        if random {
+            for i in (1..solvers.len()).rev() {
+                solvers.swap(i, rng.below(i + 1));
+            }
+            for solver in solvers.iter_mut() {
+                solver::randomize(solver, rng::Rng::new(rng.next_u64()));
+            }
         }
-        drop(solver_tx); // Close channel so workers exit when done
+
+        let num_workers = num_cpus::get();
+        println!("Spawning {} worker threads.", num_workers);
 
         // Collect and print solutions
         let mut count = 0;
-        for solution in solution_rx {
+        run_parallel(solvers, num_workers, |solution| {
+            found += 1;
+            if !keep(&solution) {
+                return true;
+            }
             println!("Solved!");
-            print_board(&solution.all, &solution);
+            print_solution(&solution, &puzzle.shapes, encode);
             count += 1;
             if count >= goal {
                 println!("Reached goal of {} solutions.", goal);
-                break;
             }
-        }
+            count < goal
+        });
+        print_unique(found, count);
+        print_stats(None, count);
     } else {
-        let mut s = create_solver(board, shapes);
+        let mut s = solver::create_solver_with_table(board, shapes.clone(), Arc::clone(&table));
+        let mut nodes = 0;
+        if random {
+            solver::randomize(&mut s, rng);
+        }
 
-        let mut handle_step_event = |e: solver::StepEvent, b: &board::Board<Point>| match e {
+        let mut handle_step_event = |e: solver::StepEvent, b: &board::Board<Square>| match e {
             solver::StepEvent::FailedToPlace => (),
             solver::StepEvent::Placed => {
+                nodes += 1;
                 if verbose {
                     println!("Placed:");
-                    print_board(&b.all, b);
+                    print_board(b);
                     println!();
                 }
             }
             solver::StepEvent::Solved => {
+                nodes += 1;
+                found += 1;
+                if !keep(b) {
+                    return;
+                }
                 println!("Solved!");
-                print_board(&b.all, b);
+                print_solution(b, &puzzle.shapes, encode);
 
                 count += 1;
                 if count >= goal {
                     println!("Reached goal of {} solutions.", goal);
+                    print_unique(found, count);
+                    print_stats(Some(nodes), count);
                     std::process::exit(0);
                 }
             }
@@ -298,5 +1317,11 @@ fn main() {
         while solver::step(&mut s, &mut handle_step_event) {
             // Continue stepping until no more steps can be taken
         }
+        print_unique(found, count);
+        print_stats(Some(nodes), count);
+
+        if count == 0 {
+            println!("No solution found; 'explain' with the same labels tells why.");
+        }
     }
 }
@@ -0,0 +1,88 @@
+use std::fmt::Write;
+
+use crate::board::Board;
+use crate::geometry::Point;
+use crate::stringify::format_placement;
+use crate::table::PlacementTable;
+use crate::topology::Square;
+
+fn column(p: &Point) -> String {
+    format!("{},{}", p.x, p.y)
+}
+
+// The exact cover problem in the text format read by Knuth's DLX programs:
+// a line of primary columns (pieces, then open cells) with any secondary
+// columns after a '|', then one row per placement. Lines starting with '|'
+// are comments; the one before each row names it piece:orientation@anchor,
+// as in the SAT mapping files. Secondary cells may be left uncovered
+pub fn to_matrix(
+    table: &PlacementTable<Square>,
+    board: &Board<Square>,
+    secondary: &[Point],
+) -> String {
+    let mut cells: Vec<Point> = board.remaining().into_iter().cloned().collect();
+    cells.sort_by_key(|p| (p.y, p.x));
+    let (open, _) = table.open_entries(board);
+
+    let mut primary: Vec<String> = table.labels.clone();
+    primary.extend(cells.iter().filter(|p| !secondary.contains(p)).map(column));
+    let mut secondary: Vec<String> = cells
+        .iter()
+        .filter(|p| secondary.contains(p))
+        .map(column)
+        .collect();
+
+    // Copies of a piece are kept in order by one column per table cell and
+    // pair of copies: the earlier copy covers the ones up to its first cell,
+    // the later copy those from its first cell on, so they clash unless the
+    // later one comes after
+    let order = |piece: usize, cell: usize| {
+        format!("{}<{}", table.labels[piece], column(&table.cells[cell]))
+    };
+    let mut orders: Vec<Vec<String>> = vec![vec![]; open.len()];
+    for piece in 0..table.labels.len() {
+        let next = match table.next_copy(piece) {
+            Some(next) => next,
+            None => continue,
+        };
+        secondary.extend((0..table.cells.len()).map(|cell| order(piece, cell)));
+        for (row, &id) in open.iter().enumerate() {
+            let entry = &table.entries[id];
+            if entry.piece == piece {
+                orders[row].extend((0..=entry.first).map(|cell| order(piece, cell)));
+            } else if entry.piece == next {
+                orders[row].extend((entry.first..table.cells.len()).map(|cell| order(piece, cell)));
+            }
+        }
+    }
+
+    let mut out = format!(
+        "| {} primary columns, {} secondary, {} rows\n",
+        primary.len(),
+        secondary.len(),
+        open.len()
+    );
+    out.push_str(&primary.join(" "));
+    if !secondary.is_empty() {
+        let _ = write!(out, " | {}", secondary.join(" "));
+    }
+    out.push('\n');
+
+    for (&id, orders) in open.iter().zip(&orders) {
+        let entry = &table.entries[id];
+        let pl = &entry.placement;
+        let mut ps = entry.points.clone();
+        ps.sort_by_key(|p| (p.y, p.x));
+        let mut ps: Vec<String> = ps.iter().map(column).collect();
+        ps.extend(orders.iter().cloned());
+        let _ = writeln!(
+            out,
+            "| {}\n{} {}",
+            format_placement(pl),
+            pl.label,
+            ps.join(" ")
+        );
+    }
+
+    out
+}
@@ -0,0 +1,279 @@
+use crate::board::Board;
+use crate::geometry::Shape;
+use crate::table::PlacementTable;
+use crate::topology::Topology;
+
+// What is stored for a sub-board: its free cells and remaining pieces as
+// bitmasks over the placement table, and how many ways there are to finish
+#[derive(Clone, Copy)]
+struct Slot {
+    free: u128,
+    pieces: u64,
+    count: u64,
+}
+
+// A transposition table of fixed size. Each position hashes to one slot and
+// a newer result simply replaces what was there, so memory never grows past
+// what was asked for
+pub struct Memo {
+    slots: Vec<Option<Slot>>,
+    pub lookups: usize,
+    pub hits: usize,
+    pub stores: usize,
+    pub replaced: usize,
+}
+
+// Size of the transposition table when none is asked for
+pub const DEFAULT_MEGABYTES: usize = 64;
+
+pub fn create_memo(megabytes: usize) -> Memo {
+    let count = (megabytes << 20) / std::mem::size_of::<Option<Slot>>();
+    Memo {
+        slots: vec![None; count.max(1)],
+        lookups: 0,
+        hits: 0,
+        stores: 0,
+        replaced: 0,
+    }
+}
+
+impl Memo {
+    pub fn bytes(&self) -> usize {
+        self.slots.len() * std::mem::size_of::<Option<Slot>>()
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.lookups as f64
+        }
+    }
+}
+
+fn slot_index(memo: &Memo, free: u128, pieces: u64) -> usize {
+    // splitmix64 finalizer over both halves of the key
+    let mut h = (free as u64)
+        ^ ((free >> 64) as u64).rotate_left(32)
+        ^ pieces.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^= h >> 31;
+    (h % memo.slots.len() as u64) as usize
+}
+
+fn probe(memo: &mut Memo, free: u128, pieces: u64) -> Option<u64> {
+    memo.lookups += 1;
+    let slot = memo.slots[slot_index(memo, free, pieces)]?;
+    if slot.free == free && slot.pieces == pieces {
+        memo.hits += 1;
+        Some(slot.count)
+    } else {
+        None
+    }
+}
+
+fn store(memo: &mut Memo, free: u128, pieces: u64, count: u64) {
+    let i = slot_index(memo, free, pieces);
+    if memo.slots[i].is_some() {
+        memo.replaced += 1;
+    }
+    memo.slots[i] = Some(Slot {
+        free,
+        pieces,
+        count,
+    });
+    memo.stores += 1;
+}
+
+struct Search<'a, T: Topology> {
+    table: &'a PlacementTable<T>,
+    // cells covered by each table entry
+    masks: Vec<u128>,
+    // copies listed after each piece, which have to be used up before it
+    later: Vec<u64>,
+    memo: Option<&'a mut Memo>,
+    nodes: usize,
+}
+
+// Always covers the last free cell, so every solution is reached exactly
+// once and a sub-board's count only depends on its free cells and pieces.
+// Going from the end of the board means dates that differ only in the
+// weekday, at the bottom of the calendar, share sub-boards once those rows
+// are covered
+fn search<T: Topology>(s: &mut Search<T>, free: u128, pieces: u64) -> u64 {
+    if free == 0 || pieces == 0 {
+        return (free == 0 && pieces == 0) as u64;
+    }
+    if let Some(count) = s.memo.as_mut().and_then(|memo| probe(memo, free, pieces)) {
+        return count;
+    }
+
+    let cell = 127 - free.leading_zeros() as usize;
+    let mut count = 0;
+    for &id in &s.table.by_cell[cell] {
+        let piece = s.table.entries[id].piece;
+        let mask = s.masks[id];
+        if pieces & 1 << piece != 0 && pieces & s.later[piece] == 0 && mask & !free == 0 {
+            s.nodes += 1;
+            count += search(s, free & !mask, pieces & !(1 << piece));
+        }
+    }
+
+    if let Some(memo) = s.memo.as_mut() {
+        store(memo, free, pieces, count);
+    }
+    count
+}
+
+// Counts the ways the pieces cover the open cells of the board without
+// listing them, optionally remembering sub-boards already counted. Returns
+// the count and the placements tried
+pub fn count_solutions<T: Topology>(
+    table: &PlacementTable<T>,
+    board: &Board<T>,
+    shapes: &[(String, Shape<T::Point>)],
+    memo: Option<&mut Memo>,
+) -> Result<(u64, usize), String> {
+    if table.cells.len() > 128 || table.labels.len() > 64 {
+        return Err("Counting handles at most 128 cells and 64 pieces.".to_string());
+    }
+
+    let masks = table
+        .entries
+        .iter()
+        .map(|entry| {
+            entry
+                .points
+                .iter()
+                .fold(0, |mask, p| mask | 1 << table.cell(p).unwrap())
+        })
+        .collect();
+
+    let mut free = 0;
+    for (i, p) in table.cells.iter().enumerate() {
+        if board.at(p).is_none() {
+            free |= 1 << i;
+        }
+    }
+    let mut pieces = 0;
+    for (label, _) in shapes {
+        let piece = table
+            .piece(label)
+            .ok_or_else(|| format!("Piece '{}' is not in the placement table.", label))?;
+        pieces |= 1 << piece;
+    }
+
+    let later = (0..table.labels.len())
+        .map(|piece| {
+            (piece + 1..table.labels.len())
+                .filter(|&other| table.kinds[other] == table.kinds[piece])
+                .fold(0, |mask, other| mask | 1 << other)
+        })
+        .collect();
+
+    let mut s = Search {
+        table,
+        masks,
+        later,
+        memo,
+        nodes: 0,
+    };
+    let count = search(&mut s, free, pieces);
+    Ok((count, s.nodes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::hint::place_pieces;
+    use crate::puzzle::{calendar, parse_puzzle};
+    use crate::solver::{create_solver_with_table, solve_first, step, StepEvent};
+
+    fn enumerated<T: Topology>(
+        table: &Arc<PlacementTable<T>>,
+        board: &Board<T>,
+        shapes: &[(String, Shape<T::Point>)],
+    ) -> u64 {
+        let mut solver =
+            create_solver_with_table(board.clone(), shapes.to_vec(), Arc::clone(table));
+        let mut listed = 0;
+        while step(&mut solver, |e, _| {
+            if let StepEvent::Solved = e {
+                listed += 1;
+            }
+        }) {}
+        listed
+    }
+
+    // Counts with and without the memo, both checked against enumeration
+    fn check<T: Topology>(
+        table: &Arc<PlacementTable<T>>,
+        board: &Board<T>,
+        shapes: &[(String, Shape<T::Point>)],
+    ) -> u64 {
+        let expected = enumerated(table, board, shapes);
+        let (plain, _) = count_solutions(table, board, shapes, None).unwrap();
+        let mut memo = create_memo(1);
+        let (memoised, _) = count_solutions(table, board, shapes, Some(&mut memo)).unwrap();
+        assert_eq!(plain, expected);
+        assert_eq!(memoised, expected);
+        assert!(memo.stores > 0);
+        expected
+    }
+
+    #[test]
+    fn counts_pentominoes_in_a_rectangle() {
+        let mut text = "board 2\n".to_string() + &"o o o o o\n".repeat(4);
+        for (label, drawing) in [
+            ("L", "****\n*"),
+            ("P", "***\n**"),
+            ("T", "***\n *\n *"),
+            ("Y", "****\n *"),
+        ] {
+            text += &format!("piece {}\n{}\n", label, drawing);
+        }
+        let puzzle = parse_puzzle(&text).unwrap();
+        let board = puzzle.make_board(&[]).unwrap();
+
+        assert_eq!(check(&puzzle.placement_table(), &board, &puzzle.shapes), 4);
+    }
+
+    // A whole date takes minutes to enumerate, so the first pieces of a
+    // solution stay on the board and the rest are counted
+    #[test]
+    fn counts_the_rest_of_a_calendar_date() {
+        let puzzle = calendar();
+        let table = puzzle.placement_table();
+        let date = ["Jan", "1", "Mon"].map(|s| s.to_string());
+        let mut board = puzzle.make_board(&date).unwrap();
+
+        let solution = solve_first(board.clone(), puzzle.shapes.clone()).unwrap();
+        place_pieces(&mut board, &puzzle.shapes, &solution[..5]).unwrap();
+        let rest: Vec<_> = puzzle
+            .shapes
+            .iter()
+            .filter(|(label, _)| solution[..5].iter().all(|pl| pl.label != *label))
+            .cloned()
+            .collect();
+
+        assert!(check(&table, &board, &rest) > 0);
+    }
+
+    #[test]
+    #[ignore = "enumerates a whole date, which takes minutes"]
+    fn counts_a_calendar_date() {
+        let puzzle = calendar();
+        let date = ["Jan", "1", "Mon"].map(|s| s.to_string());
+        let board = puzzle.make_board(&date).unwrap();
+
+        assert_eq!(
+            check(&puzzle.placement_table(), &board, &puzzle.shapes),
+            857
+        );
+    }
+}
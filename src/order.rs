@@ -0,0 +1,109 @@
+use std::cmp::Reverse;
+use std::sync::Arc;
+
+use crate::board::Board;
+use crate::geometry::Shape;
+use crate::solver::{create_solver_with_table, step, StepEvent};
+use crate::table::PlacementTable;
+use crate::topology::Topology;
+
+// How the pieces are lined up before the search places them one by one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PieceOrder {
+    // as the puzzle lists them
+    Given,
+    // fewest legal placements on the date's board first
+    Fewest,
+    // most cells first
+    Largest,
+    // whichever of the others does best in a short trial search
+    Pilot,
+}
+
+// Placements each order gets to try in a pilot search
+const PILOT_NODES: usize = 100_000;
+
+// How many ways each piece fits on the open cells of the board
+pub fn placement_counts<T: Topology>(
+    table: &PlacementTable<T>,
+    board: &Board<T>,
+    shapes: &[(String, Shape<T::Point>)],
+) -> Vec<usize> {
+    let (open, _) = table.open_entries(board);
+    let mut counts = vec![0; table.labels.len()];
+    for id in open {
+        counts[table.entries[id].piece] += 1;
+    }
+
+    shapes
+        .iter()
+        .map(|(label, _)| table.piece(label).map_or(0, |piece| counts[piece]))
+        .collect()
+}
+
+pub fn labels<P: Clone>(shapes: &[(String, Shape<P>)]) -> Vec<String> {
+    shapes.iter().map(|(label, _)| label.clone()).collect()
+}
+
+// Solutions found within the pilot budget, and after how many placements
+// the first one came; more solutions and an earlier first one are better
+fn pilot<T: Topology>(
+    table: &Arc<PlacementTable<T>>,
+    board: &Board<T>,
+    shapes: &[(String, Shape<T::Point>)],
+) -> (usize, usize) {
+    let mut solver = create_solver_with_table(board.clone(), shapes.to_vec(), Arc::clone(table));
+    let mut solutions = 0;
+    let mut first = PILOT_NODES;
+    let mut nodes = 0;
+
+    while nodes < PILOT_NODES
+        && step(&mut solver, |e, _| match e {
+            StepEvent::FailedToPlace => (),
+            StepEvent::Placed => nodes += 1,
+            StepEvent::Solved => {
+                nodes += 1;
+                solutions += 1;
+                first = first.min(nodes);
+            }
+        })
+    {}
+
+    (solutions, first)
+}
+
+// The pieces in the order the search should place them. Sorting is stable,
+// so ties keep the puzzle's order
+pub fn order_pieces<T: Topology>(
+    table: &Arc<PlacementTable<T>>,
+    board: &Board<T>,
+    shapes: &[(String, Shape<T::Point>)],
+    order: PieceOrder,
+) -> Vec<(String, Shape<T::Point>)> {
+    let counts = placement_counts(table, board, shapes);
+    let mut indices: Vec<usize> = (0..shapes.len()).collect();
+
+    match order {
+        PieceOrder::Given => (),
+        PieceOrder::Fewest => indices.sort_by_key(|&i| counts[i]),
+        PieceOrder::Largest => indices.sort_by_key(|&i| Reverse(shapes[i].1.points.len())),
+        PieceOrder::Pilot => {
+            let mut candidates: Vec<Vec<(String, Shape<T::Point>)>> = vec![];
+            for order in [PieceOrder::Given, PieceOrder::Fewest, PieceOrder::Largest] {
+                let candidate = order_pieces(table, board, shapes, order);
+                if !candidates.iter().any(|c| labels(c) == labels(&candidate)) {
+                    candidates.push(candidate);
+                }
+            }
+
+            // the first of equally good candidates wins
+            let best = (0..candidates.len())
+                .map(|i| (i, pilot(table, board, &candidates[i])))
+                .min_by_key(|&(_, (solutions, first))| (Reverse(solutions), first))
+                .map_or(0, |(i, _)| i);
+            return candidates.swap_remove(best);
+        }
+    }
+
+    indices.into_iter().map(|i| shapes[i].clone()).collect()
+}
@@ -1,31 +1,40 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
 use crate::board::Board;
 
-use crate::geometry::Point;
 use crate::geometry::Shape;
 
-use crate::solver::create_solver;
+use crate::solver::create_solver_with_table;
 use crate::solver::step;
 use crate::solver::Solver;
 use crate::solver::StepEvent;
+use crate::table::PlacementTable;
+use crate::topology::Topology;
 
-pub fn create_parallel_solver(
-    board: Board<Point>,
-    pieces: Vec<(String, Shape<Point>)>,
+// Splits the search after the first n pieces, or all of them if there are
+// fewer; all solvers share the table
+pub fn create_parallel_solver<T: Topology>(
+    board: Board<T>,
+    pieces: Vec<(String, Shape<T::Point>)>,
     n: usize,
-) -> Vec<Solver<Point>> {
+    table: &Arc<PlacementTable<T>>,
+) -> Vec<Solver<T>> {
     let mut solvers = Vec::new();
 
-    let (first_n, remainder) = pieces.split_at(n);
+    let (first_n, remainder) = pieces.split_at(n.min(pieces.len()));
     let first_n_vec = first_n.to_vec();
     let remainder_vec = remainder.to_vec();
 
-    let mut solver = create_solver(board.clone(), first_n_vec);
+    let mut solver = create_solver_with_table(board.clone(), first_n_vec, Arc::clone(table));
 
-    let mut handle_step_event = |e: StepEvent, b: &Board<Point>| match e {
+    let mut handle_step_event = |e: StepEvent, b: &Board<T>| match e {
         StepEvent::FailedToPlace => (),
         StepEvent::Placed => (),
         StepEvent::Solved => {
-            let new_solver = create_solver(b.clone(), remainder_vec.clone());
+            let new_solver =
+                create_solver_with_table(b.clone(), remainder_vec.clone(), Arc::clone(table));
             solvers.push(new_solver);
         }
     };
@@ -36,3 +45,107 @@ pub fn create_parallel_solver(
 
     solvers
 }
+
+// Hands the solvers out to worker threads and passes every solution found to
+// `on_solution` on the calling thread until it returns false or all are done
+pub fn run_parallel<T, F>(solvers: Vec<Solver<T>>, workers: usize, mut on_solution: F)
+where
+    T: Topology + Send + Sync + 'static,
+    T::Point: Send + Sync,
+    F: FnMut(Board<T>) -> bool,
+{
+    let (solver_tx, solver_rx) = mpsc::channel();
+    let (solution_tx, solution_rx) = mpsc::channel();
+
+    let solver_rx = Arc::new(Mutex::new(solver_rx));
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // Spawn worker threads
+    for _ in 0..workers {
+        let solver_rx = Arc::clone(&solver_rx);
+        let solution_tx = solution_tx.clone();
+        let stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            loop {
+                // Lock and receive a solver
+                let maybe_solver = {
+                    let lock = solver_rx.lock().unwrap();
+                    lock.recv()
+                };
+                match maybe_solver {
+                    Ok(mut solver) => {
+                        let mut handle_step_event = |e: StepEvent, b: &Board<T>| {
+                            if let StepEvent::Solved = e {
+                                let _ = solution_tx.send(b.clone());
+                            }
+                        };
+                        while !stop.load(Ordering::Relaxed)
+                            && step(&mut solver, &mut handle_step_event)
+                        {}
+                    }
+                    Err(_) => break, // Channel closed, exit thread
+                }
+            }
+        });
+    }
+
+    drop(solution_tx); // close channel so the loop below ends when workers are done
+
+    // Send solvers to workers
+    for solver in solvers {
+        solver_tx.send(solver).unwrap();
+    }
+    drop(solver_tx); // Close channel so workers exit when done
+
+    for solution in solution_rx {
+        if !on_solution(solution) {
+            stop.store(true, Ordering::Relaxed);
+            break;
+        }
+    }
+}
+
+// Runs `job` on every input on worker threads, passing the results to
+// `on_result` on the calling thread in the order they finish
+pub fn run_jobs<I, O, J, F>(inputs: Vec<I>, workers: usize, job: J, mut on_result: F)
+where
+    I: Send + 'static,
+    O: Send + 'static,
+    J: Fn(I) -> O + Send + Sync + 'static,
+    F: FnMut(O),
+{
+    let (input_tx, input_rx) = mpsc::channel();
+    let (result_tx, result_rx) = mpsc::channel();
+
+    let input_rx = Arc::new(Mutex::new(input_rx));
+    let job = Arc::new(job);
+
+    for _ in 0..workers {
+        let input_rx = Arc::clone(&input_rx);
+        let result_tx = result_tx.clone();
+        let job = Arc::clone(&job);
+        thread::spawn(move || loop {
+            let maybe_input = {
+                let lock = input_rx.lock().unwrap();
+                lock.recv()
+            };
+            match maybe_input {
+                Ok(input) => {
+                    let _ = result_tx.send(job(input));
+                }
+                Err(_) => break,
+            }
+        });
+    }
+
+    drop(result_tx);
+
+    for input in inputs {
+        input_tx.send(input).unwrap();
+    }
+    drop(input_tx);
+
+    for result in result_rx {
+        on_result(result);
+    }
+}
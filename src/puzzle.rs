@@ -0,0 +1,822 @@
+use std::sync::Arc;
+
+use crate::board::{make_point_board, Board};
+use crate::enumerate::{attrs, Symmetry};
+use crate::geometry::{
+    HexPoint, LabeledPoint, LabeledPoints, Point, Point3, Shape, ShapeAttrs, TriPoint, VisualShape,
+};
+use crate::stringify::{
+    convert_to_cube_points, convert_to_cube_shape, convert_to_hex_points, convert_to_hex_shape,
+    convert_to_labeled_points, convert_to_shape, convert_to_strings, convert_to_tri_points,
+    convert_to_tri_shape,
+};
+use crate::table::{create_placement_table, PlacementTable};
+use crate::topology::Square;
+
+// A board layout together with the pieces that have to be placed on it
+#[derive(Clone)]
+pub struct Puzzle<P: Clone = Point> {
+    pub cells: LabeledPoints<P>,
+    pub shapes: Vec<(String, Shape<P>)>,
+    // a date picks one label out of each group
+    pub groups: Vec<Vec<String>>,
+}
+
+impl<P: Copy> Puzzle<P> {
+    pub fn points(&self) -> Vec<P> {
+        self.cells.iter().map(|lp| lp.point).collect()
+    }
+
+    pub fn find(&self, label: &str) -> Option<P> {
+        self.cells
+            .iter()
+            .find(|lp| lp.label == label)
+            .map(|lp| lp.point)
+    }
+
+    // Every combination of one label per group
+    pub fn dates(&self) -> Vec<Vec<String>> {
+        let mut dates = vec![vec![]];
+
+        for group in &self.groups {
+            dates = dates
+                .iter()
+                .flat_map(|date| {
+                    group.iter().map(move |label| {
+                        let mut next = date.clone();
+                        next.push(label.clone());
+                        next
+                    })
+                })
+                .collect();
+        }
+
+        dates
+    }
+}
+
+impl Puzzle {
+    // Creates an empty board with the cells of the given labels blocked out
+    pub fn make_board(&self, labels: &[String]) -> Result<Board<Square>, String> {
+        let mut board = make_point_board(self.points());
+
+        for label in labels {
+            if let Some(p) = self.find(label) {
+                board.fill(&vec![p], Point { x: 0, y: 0 }, "*");
+            } else {
+                return Err(format!("Label '{}' not found.", label));
+            }
+        }
+
+        Ok(board)
+    }
+
+    // Placements of every piece on the full board, shared by all dates
+    pub fn placement_table(&self) -> Arc<PlacementTable<Square>> {
+        create_placement_table(&make_point_board(self.points()), &self.shapes)
+    }
+
+    // FNV-1a hash over the cells, groups and pieces, stable across runs and
+    // platforms. Every list and label goes in after its length, so moving
+    // bytes from one field to the next changes the hash
+    pub fn checksum(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |bytes: &[u8]| {
+            for b in bytes {
+                hash ^= *b as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+        let len = |n: usize| (n as u64).to_le_bytes();
+
+        feed(&len(self.cells.len()));
+        for lp in &self.cells {
+            feed(&len(lp.label.len()));
+            feed(lp.label.as_bytes());
+            feed(&lp.point.x.to_le_bytes());
+            feed(&lp.point.y.to_le_bytes());
+        }
+        feed(&len(self.groups.len()));
+        for group in &self.groups {
+            feed(&len(group.len()));
+            for label in group {
+                feed(&len(label.len()));
+                feed(label.as_bytes());
+            }
+        }
+        feed(&len(self.shapes.len()));
+        for (label, shape) in &self.shapes {
+            feed(&len(label.len()));
+            feed(label.as_bytes());
+            feed(&[shape.attrs.chiral as u8, shape.attrs.rotations]);
+            feed(&len(shape.points.len()));
+            for p in &shape.points {
+                feed(&p.x.to_le_bytes());
+                feed(&p.y.to_le_bytes());
+            }
+        }
+
+        hash
+    }
+}
+
+fn to_strings(ps: &[&str]) -> Vec<String> {
+    ps.iter().map(|s| s.to_string()).collect()
+}
+
+fn unlabeled<P>(points: Vec<P>) -> LabeledPoints<P> {
+    points
+        .into_iter()
+        .map(|point| LabeledPoint {
+            label: String::new(),
+            point,
+        })
+        .collect()
+}
+
+fn piece(points: &[&str], chiral: bool, rotations: u8) -> Shape<Point> {
+    convert_to_shape(&VisualShape {
+        points: to_strings(points),
+        attrs: ShapeAttrs { chiral, rotations },
+    })
+}
+
+pub fn calendar() -> Puzzle {
+    let cells = convert_to_labeled_points(
+        &[
+            "Jan Feb Mar Apr May Jun ",
+            "Jul Aug Sep Oct Nov Dec ",
+            "  1   2   3   4   5   6   7 ",
+            "  8   9  10  11  12  13  14 ",
+            " 15  16  17  18  19  20  21 ",
+            " 22  23  24  25  26  27  28 ",
+            " 29  30  31 Sun Mon Tue Wed ",
+            "                Thu Fri Sat ",
+        ],
+        4,
+    );
+
+    let l_piece = piece(
+        &[
+            "***", //
+            "*",   //
+        ],
+        true,
+        3,
+    );
+
+    let j_piece = piece(
+        &[
+            "****", //
+            "*",    //
+        ],
+        true,
+        3,
+    );
+
+    let i_piece = piece(&["****"], false, 1);
+
+    let p_piece = piece(
+        &[
+            "***", //
+            "**",  //
+        ],
+        true,
+        3,
+    );
+
+    let n_piece = piece(
+        &[
+            "**",   //
+            " ***", //
+        ],
+        true,
+        3,
+    );
+
+    let u_piece = piece(
+        &[
+            "* *", //
+            "***", //
+        ],
+        false,
+        3,
+    );
+
+    let t_piece = piece(
+        &[
+            "***", //
+            " * ", //
+            " * ", //
+        ],
+        false,
+        3,
+    );
+
+    let v_piece = piece(
+        &[
+            "***", //
+            "*",   //
+            "*",   //
+        ],
+        false,
+        3,
+    );
+
+    let z_piece = piece(
+        &[
+            "**",  //
+            " *",  //
+            " **", //
+        ],
+        true,
+        1,
+    );
+
+    let s_piece = piece(
+        &[
+            "**",  //
+            " **", //
+        ],
+        true,
+        1,
+    );
+
+    let months = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days: Vec<String> = (1..=31).map(|d| d.to_string()).collect();
+    let weekdays = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+    Puzzle {
+        cells,
+        groups: vec![to_strings(&months), days, to_strings(&weekdays)],
+        shapes: vec![
+            ("Z".to_string(), z_piece),
+            ("V".to_string(), v_piece),
+            ("U".to_string(), u_piece),
+            ("T".to_string(), t_piece),
+            ("P".to_string(), p_piece),
+            ("N".to_string(), n_piece),
+            ("L".to_string(), l_piece),
+            ("J".to_string(), j_piece),
+            ("I".to_string(), i_piece),
+            ("S".to_string(), s_piece),
+        ],
+    }
+}
+
+fn hex_piece(points: &[&str], chiral: bool, rotations: u8) -> Shape<HexPoint> {
+    convert_to_hex_shape(&VisualShape {
+        points: to_strings(points),
+        attrs: ShapeAttrs { chiral, rotations },
+    })
+}
+
+// The seven tetrahexes on a board of four staggered rows
+pub fn polyhex() -> Puzzle<HexPoint> {
+    let cells = convert_to_hex_points(
+        &[
+            "* * * * * * *",  //
+            " * * * * * * *", //
+            "* * * * * * *",  //
+            " * * * * * * *", //
+        ],
+        " ",
+    );
+
+    let bar = hex_piece(&["* * * *"], false, 2);
+
+    let pistol = hex_piece(
+        &[
+            " *",  //
+            "*",   //
+            " *",  //
+            "  *", //
+        ],
+        true,
+        5,
+    );
+
+    let wave = hex_piece(
+        &[
+            "* *", //
+            " *",  //
+            "  *", //
+        ],
+        true,
+        5,
+    );
+
+    let worm = hex_piece(
+        &[
+            "*",  //
+            " *", //
+            "*",  //
+            " *", //
+        ],
+        true,
+        2,
+    );
+
+    let bee = hex_piece(
+        &[
+            " *",  //
+            "* *", //
+            " *",  //
+        ],
+        false,
+        2,
+    );
+
+    let arch = hex_piece(
+        &[
+            " *",   //
+            "*",    //
+            " * *", //
+        ],
+        false,
+        5,
+    );
+
+    let propeller = hex_piece(
+        &[
+            "*",    //
+            " * *", //
+            "*",    //
+        ],
+        false,
+        1,
+    );
+
+    Puzzle {
+        cells: unlabeled(cells),
+        groups: vec![],
+        shapes: vec![
+            ("A".to_string(), arch),
+            ("B".to_string(), bee),
+            ("I".to_string(), bar),
+            ("P".to_string(), pistol),
+            ("Y".to_string(), propeller),
+            ("W".to_string(), wave),
+            ("S".to_string(), worm),
+        ],
+    }
+}
+
+fn tri_piece(points: &[&str], chiral: bool, rotations: u8) -> Shape<TriPoint> {
+    convert_to_tri_shape(&VisualShape {
+        points: to_strings(points),
+        attrs: ShapeAttrs { chiral, rotations },
+    })
+}
+
+// The three tetriamonds and four pentiamonds on four rows of eight triangles
+pub fn polyiamond() -> Puzzle<TriPoint> {
+    let cells = convert_to_tri_points(
+        &[
+            "********", //
+            "********", //
+            "********", //
+            "********", //
+        ],
+        " ",
+    );
+
+    let bar = tri_piece(
+        &[
+            "  *", //
+            " **", //
+            " *",  //
+        ],
+        true,
+        2,
+    );
+
+    let tee = tri_piece(
+        &[
+            "  *",  //
+            " ***", //
+        ],
+        false,
+        1,
+    );
+
+    let chevron = tri_piece(
+        &[
+            "**", //
+            "**", //
+        ],
+        false,
+        5,
+    );
+
+    let lightning = tri_piece(
+        &[
+            "  *", //
+            " **", //
+            "**",  //
+        ],
+        false,
+        5,
+    );
+
+    let crook = tri_piece(
+        &[
+            "  *", //
+            " **", //
+            " **", //
+        ],
+        true,
+        5,
+    );
+
+    let arrow = tri_piece(
+        &[
+            "  *",  //
+            " ***", //
+            " *",   //
+        ],
+        true,
+        5,
+    );
+
+    let trapezoid = tri_piece(
+        &[
+            "***", //
+            "**",  //
+        ],
+        false,
+        5,
+    );
+
+    Puzzle {
+        cells: unlabeled(cells),
+        groups: vec![],
+        shapes: vec![
+            ("I".to_string(), bar),
+            ("T".to_string(), tee),
+            ("C".to_string(), chevron),
+            ("L".to_string(), lightning),
+            ("K".to_string(), crook),
+            ("A".to_string(), arrow),
+            ("Z".to_string(), trapezoid),
+        ],
+    }
+}
+
+fn cube_piece(points: &[&str]) -> Shape<Point3> {
+    convert_to_cube_shape(&VisualShape {
+        points: to_strings(points),
+        // every rotation is tried and duplicates dropped; solid pieces are
+        // never mirrored
+        attrs: ShapeAttrs {
+            chiral: false,
+            rotations: 23,
+        },
+    })
+}
+
+// The seven Soma pieces and the 3x3x3 cube they assemble into
+pub fn soma() -> Puzzle<Point3> {
+    let cells = convert_to_cube_points(
+        &[
+            "***", "***", "***", "", //
+            "***", "***", "***", "", //
+            "***", "***", "***", //
+        ],
+        ".",
+    );
+
+    let v = cube_piece(&[
+        "**", //
+        "*",  //
+    ]);
+    let l = cube_piece(&[
+        "***", //
+        "*",   //
+    ]);
+    let t = cube_piece(&[
+        "***", //
+        ".*",  //
+    ]);
+    let z = cube_piece(&[
+        "**",  //
+        ".**", //
+    ]);
+    let a = cube_piece(&[
+        "**", "*", "", //
+        "..", "*", //
+    ]);
+    let b = cube_piece(&[
+        "**", "*", "",   //
+        ".*", //
+    ]);
+    let p = cube_piece(&[
+        "**", "*", "",  //
+        "*", //
+    ]);
+
+    Puzzle {
+        cells: unlabeled(cells),
+        groups: vec![],
+        shapes: vec![
+            ("P".to_string(), p),
+            ("A".to_string(), a),
+            ("B".to_string(), b),
+            ("Z".to_string(), z),
+            ("T".to_string(), t),
+            ("L".to_string(), l),
+            ("V".to_string(), v),
+        ],
+    }
+}
+
+// The label and number of copies after "piece", as in "piece I x3"
+fn piece_header<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<(String, usize), String> {
+    let label = words.next().ok_or("Expected a label after 'piece'.")?;
+    let count = match words.next() {
+        Some(word) => word
+            .strip_prefix('x')
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .ok_or(format!("Expected a count like 'x2' after piece {}.", label))?,
+        None => 1,
+    };
+    Ok((label.to_string(), count))
+}
+
+// Labels for each piece and its copies. Boards are printed one character
+// per cell, so a copy is named by a character no other label starts with:
+// the lower case of its piece's for the second copy and the copy's number
+// from the third on, if free, otherwise the first free letter or digit.
+// format_puzzle lists which copies belong to which piece
+fn copy_labels(pieces: &[(String, usize)]) -> Result<Vec<Vec<String>>, String> {
+    let mut taken: Vec<char> = pieces
+        .iter()
+        .filter_map(|(label, _)| label.chars().next())
+        .collect();
+    let spare: Vec<char> = ('a'..='z').chain('0'..='9').chain('A'..='Z').collect();
+
+    let mut labels = vec![];
+    for (label, count) in pieces {
+        let mut copies = vec![label.clone()];
+        for copy in 1..*count {
+            let own = match copy {
+                1 => label.chars().next().map(|c| c.to_ascii_lowercase()),
+                _ => char::from_digit(copy as u32 + 1, 10),
+            };
+            let c = own
+                .into_iter()
+                .chain(spare.iter().cloned())
+                .find(|c| !taken.contains(c))
+                .ok_or(format!(
+                    "No label left for another copy of piece {}.",
+                    label
+                ))?;
+            taken.push(c);
+            copies.push(c.to_string());
+        }
+        labels.push(copies);
+    }
+
+    Ok(labels)
+}
+
+// Reads a solid puzzle: a "target" section with the volume to fill and one
+// "piece <label>" section per piece, each drawn in layers as for
+// convert_to_cube_points with '.' as the blank. "piece <label> x<count>"
+// stands for that many copies
+pub fn cube_puzzle(text: &str) -> Result<Puzzle<Point3>, String> {
+    let mut target: Option<Vec<&str>> = None;
+    let mut pieces: Vec<((String, usize), Vec<&str>)> = vec![];
+    let mut in_target = false;
+
+    for line in text.lines() {
+        if line.trim() == "target" {
+            if target.is_some() {
+                return Err("More than one target section.".to_string());
+            }
+            target = Some(vec![]);
+            in_target = true;
+        } else if let Some(header) = line.trim().strip_prefix("piece ") {
+            pieces.push((piece_header(header.split_whitespace())?, vec![]));
+            in_target = false;
+        } else if let (true, Some(lines)) = (in_target, &mut target) {
+            lines.push(line);
+        } else if let Some((_, lines)) = pieces.last_mut() {
+            lines.push(line);
+        } else if !line.trim().is_empty() {
+            return Err(format!("Expected 'target' or 'piece', found '{}'.", line));
+        }
+    }
+
+    let target = target.ok_or("Missing target section.")?;
+    let cells = convert_to_cube_points(&target, ".");
+    if cells.is_empty() {
+        return Err("Target has no cells.".to_string());
+    }
+
+    let headers: Vec<(String, usize)> = pieces.iter().map(|(header, _)| header.clone()).collect();
+    let mut shapes = vec![];
+    for (labels, (_, lines)) in copy_labels(&headers)?.into_iter().zip(pieces) {
+        if convert_to_cube_points(&lines, ".").is_empty() {
+            return Err(format!("Piece {} has no cells.", labels[0]));
+        }
+        let shape = cube_piece(&lines);
+        shapes.extend(labels.into_iter().map(|label| (label, shape.clone())));
+    }
+    if shapes.is_empty() {
+        return Err("No pieces defined.".to_string());
+    }
+
+    Ok(Puzzle {
+        cells: unlabeled(cells),
+        groups: vec![],
+        shapes,
+    })
+}
+
+// Reads a square grid puzzle. "board <width>" starts the layout, written as
+// rows of labels each taking up `width` characters; every "group" line lists
+// the labels a date picks one of; "piece <label>" is followed by a drawing of
+// the piece with '*', or "piece <label> x<count>" for several copies of it.
+// Pieces may be flipped and turned, and lines starting with '#' are comments.
+// The pieces must cover the board less one cell per group
+pub fn parse_puzzle(text: &str) -> Result<Puzzle, String> {
+    let mut board: Option<(usize, Vec<String>)> = None;
+    let mut groups = vec![];
+    let mut pieces: Vec<((String, usize), Vec<String>)> = vec![];
+    let mut in_board = false;
+
+    for line in text.lines().filter(|line| !line.starts_with('#')) {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("board") => {
+                if board.is_some() {
+                    return Err("More than one board section.".to_string());
+                }
+                let width = words
+                    .next()
+                    .and_then(|w| w.parse::<usize>().ok())
+                    .filter(|w| *w > 0)
+                    .ok_or("Expected a cell width after 'board'.")?;
+                board = Some((width, vec![]));
+                in_board = true;
+            }
+            Some("group") => {
+                groups.push(words.map(|w| w.to_string()).collect());
+                in_board = false;
+            }
+            Some("piece") => {
+                pieces.push((piece_header(words)?, vec![]));
+                in_board = false;
+            }
+            _ => match (&mut board, pieces.last_mut()) {
+                (Some((width, rows)), _) if in_board => {
+                    // pad to whole cells so a short last label is kept
+                    let cells = line.len().div_ceil(*width);
+                    rows.push(format!("{:<1$}", line, cells * *width));
+                }
+                (_, Some((_, lines))) => lines.push(line.to_string()),
+                _ if line.trim().is_empty() => (),
+                _ => return Err(format!("Expected 'board' or 'piece', found '{}'.", line)),
+            },
+        }
+    }
+
+    let (width, rows) = board.ok_or("Missing board section.")?;
+    let rows: Vec<&str> = rows.iter().map(|s| s.as_str()).collect();
+    let cells = convert_to_labeled_points(&rows, width);
+    if cells.is_empty() {
+        return Err("Board has no cells.".to_string());
+    }
+
+    let headers: Vec<(String, usize)> = pieces.iter().map(|(header, _)| header.clone()).collect();
+    let mut shapes = vec![];
+    for (labels, (_, mut lines)) in copy_labels(&headers)?.into_iter().zip(pieces) {
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        if lines.iter().all(|line| line.trim().is_empty()) {
+            return Err(format!("Piece {} has no cells.", labels[0]));
+        }
+
+        let mut shape = convert_to_shape(&VisualShape {
+            points: lines,
+            attrs: ShapeAttrs {
+                chiral: false,
+                rotations: 0,
+            },
+        });
+        shape.attrs = attrs(&Square, &shape.points, Symmetry::Free);
+        shapes.extend(labels.into_iter().map(|label| (label, shape.clone())));
+    }
+    if shapes.is_empty() {
+        return Err("No pieces defined.".to_string());
+    }
+
+    let puzzle = Puzzle {
+        cells,
+        shapes,
+        groups,
+    };
+    for label in puzzle.groups.iter().flatten() {
+        if puzzle.find(label).is_none() {
+            return Err(format!("Label '{}' is not on the board.", label));
+        }
+    }
+
+    // every date blocks one cell per group and the pieces cover the rest
+    let area: usize = puzzle.shapes.iter().map(|(_, s)| s.points.len()).sum();
+    let open = puzzle.cells.len() - puzzle.groups.len();
+    if area != open {
+        return Err(format!(
+            "The pieces cover {} cells but a date leaves {} open.",
+            area, open
+        ));
+    }
+
+    Ok(puzzle)
+}
+
+// Writes a puzzle in the format read by parse_puzzle
+pub fn format_puzzle(puzzle: &Puzzle) -> String {
+    let width = puzzle
+        .cells
+        .iter()
+        .map(|lp| lp.label.len())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let max_x = puzzle.cells.iter().map(|lp| lp.point.x).max().unwrap_or(0);
+    let max_y = puzzle.cells.iter().map(|lp| lp.point.y).max().unwrap_or(0);
+
+    let mut lines = vec![format!("board {}", width)];
+    for y in 0..=max_y {
+        let row: String = (0..=max_x)
+            .map(|x| {
+                let label = puzzle
+                    .cells
+                    .iter()
+                    .find(|lp| lp.point == Point { x, y })
+                    .map_or("", |lp| lp.label.as_str());
+                format!("{:>1$} ", label, width - 1)
+            })
+            .collect();
+        lines.push(row);
+    }
+
+    for group in &puzzle.groups {
+        lines.push(format!("group {}", group.join(" ")));
+    }
+    // runs of the same shape are written as copies when reading them back
+    // gives the same labels
+    let mut runs: Vec<(String, usize, &Shape<Point>)> = vec![];
+    for (label, shape) in &puzzle.shapes {
+        match runs.last_mut() {
+            Some((_, count, last)) if last.points == shape.points => *count += 1,
+            _ => runs.push((label.clone(), 1, shape)),
+        }
+    }
+    let headers: Vec<(String, usize)> = runs
+        .iter()
+        .map(|(label, count, _)| (label.clone(), *count))
+        .collect();
+    let same = copy_labels(&headers).is_ok_and(|labels| {
+        labels
+            .iter()
+            .flatten()
+            .eq(puzzle.shapes.iter().map(|(label, _)| label))
+    });
+    if !same {
+        runs = puzzle
+            .shapes
+            .iter()
+            .map(|(label, shape)| (label.clone(), 1, shape))
+            .collect();
+    }
+
+    let mut next = 0;
+    for (label, count, shape) in runs {
+        let copies: Vec<&str> = puzzle.shapes[next..next + count]
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect();
+        next += count;
+        if count > 1 {
+            // nothing else on a printed board ties the copies to the piece
+            lines.push(format!(
+                "# copies of {} are drawn as {}",
+                label,
+                copies.join(" ")
+            ));
+            lines.push(format!("piece {} x{}", label, count));
+        } else {
+            lines.push(format!("piece {}", label));
+        }
+        lines.extend(convert_to_strings(&shape.points, |_| '*'));
+    }
+
+    lines.join("\n")
+}
@@ -0,0 +1,374 @@
+use std::fmt::Write;
+
+use crate::board::Board;
+use crate::geometry::{Point, Shape};
+use crate::hint::place_pieces;
+use crate::solver::Placement;
+use crate::stringify::{format_placement, parse_placement};
+use crate::table::PlacementTable;
+use crate::topology::Square;
+
+// A formula in conjunctive normal form, literals numbered from 1 and negated
+// for "not", as in DIMACS
+pub struct Cnf {
+    pub vars: usize,
+    pub clauses: Vec<Vec<i32>>,
+}
+
+// Exactly one of the variables is true: one clause saying at least one, and
+// one per pair saying not both
+fn exactly_one(clauses: &mut Vec<Vec<i32>>, vars: &[i32]) {
+    clauses.push(vars.to_vec());
+    for (i, a) in vars.iter().enumerate() {
+        for b in &vars[i + 1..] {
+            clauses.push(vec![-a, -b]);
+        }
+    }
+}
+
+// One variable per placement that fits on the open cells, numbered in
+// table order; every open cell and every piece is used by exactly one of them
+pub fn to_cnf(
+    table: &PlacementTable<Square>,
+    board: &Board<Square>,
+) -> (Cnf, Vec<Placement<Point>>) {
+    let (open, position) = table.open_entries(board);
+    let mut clauses = vec![];
+
+    for piece in 0..table.labels.len() {
+        let vars: Vec<i32> = open
+            .iter()
+            .enumerate()
+            .filter(|(_, &id)| table.entries[id].piece == piece)
+            .map(|(i, _)| i as i32 + 1)
+            .collect();
+        exactly_one(&mut clauses, &vars);
+    }
+
+    for (cell, p) in table.cells.iter().enumerate() {
+        if board.at(p).is_some() {
+            continue;
+        }
+        let vars: Vec<i32> = table.by_cell[cell]
+            .iter()
+            .filter_map(|&id| position[id])
+            .map(|i| i as i32 + 1)
+            .collect();
+        exactly_one(&mut clauses, &vars);
+    }
+
+    // copies of a piece cover their first cells in the order listed
+    for (a, b) in table.copy_conflicts(&open) {
+        clauses.push(vec![-(a as i32 + 1), -(b as i32 + 1)]);
+    }
+
+    let cnf = Cnf {
+        vars: open.len(),
+        clauses,
+    };
+    let placements = open
+        .iter()
+        .map(|&id| table.entries[id].placement.clone())
+        .collect();
+    (cnf, placements)
+}
+
+pub fn write_dimacs(cnf: &Cnf) -> String {
+    let mut out = format!("p cnf {} {}\n", cnf.vars, cnf.clauses.len());
+    for clause in &cnf.clauses {
+        for lit in clause {
+            let _ = write!(out, "{} ", lit);
+        }
+        out.push_str("0\n");
+    }
+    out
+}
+
+// The mapping file starts with the blocked labels, then lists the placement
+// each variable stands for, e.g. "12 Z:2@3,4"
+pub fn write_mapping(labels: &[String], placements: &[Placement<Point>]) -> String {
+    let mut out = format!("labels {}\n", labels.join(" "));
+    for (i, pl) in placements.iter().enumerate() {
+        let _ = writeln!(out, "{} {}", i + 1, format_placement(pl));
+    }
+    out
+}
+
+pub fn parse_mapping(text: &str) -> Result<(Vec<String>, Vec<Placement<Point>>), String> {
+    let mut lines = text.lines();
+    let labels = lines
+        .next()
+        .and_then(|line| line.strip_prefix("labels"))
+        .ok_or("Mapping does not start with 'labels'.")?
+        .split_whitespace()
+        .map(|s| s.to_string())
+        .collect();
+
+    let mut placements = vec![];
+    for line in lines.filter(|line| !line.trim().is_empty()) {
+        let pl = line
+            .split_once(' ')
+            .filter(|(var, _)| var.parse() == Ok(placements.len() + 1))
+            .and_then(|(_, pl)| parse_placement(pl.trim()))
+            .ok_or_else(|| format!("Malformed mapping line '{}'.", line))?;
+        placements.push(pl);
+    }
+
+    Ok((labels, placements))
+}
+
+// Reads a model as printed by SAT solvers, "s" and "v" lines in competition
+// style or just the literals, returning the true variables. Ok(None) means
+// the solver reported the formula unsatisfiable
+pub fn parse_model(text: &str) -> Result<Option<Vec<usize>>, String> {
+    let mut trues = vec![];
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('c') || line.is_empty() {
+            continue;
+        }
+        if line.starts_with('s') || line == "UNSAT" || line == "SAT" {
+            if line.contains("UNSAT") {
+                return Ok(None);
+            }
+            continue;
+        }
+
+        let lits = line.strip_prefix('v').unwrap_or(line);
+        for lit in lits.split_whitespace() {
+            let lit: i64 = lit
+                .parse()
+                .map_err(|_| format!("Malformed literal '{}'.", lit))?;
+            if lit > 0 {
+                trues.push(lit as usize);
+            }
+        }
+    }
+
+    Ok(Some(trues))
+}
+
+// Places the pieces of the true variables on the board
+pub fn model_to_board(
+    board: &Board<Square>,
+    shapes: &[(String, Shape<Point>)],
+    placements: &[Placement<Point>],
+    trues: &[usize],
+) -> Result<Board<Square>, String> {
+    let chosen = trues
+        .iter()
+        .map(|var| {
+            placements
+                .get(var.wrapping_sub(1))
+                .cloned()
+                .ok_or_else(|| format!("Variable {} is not in the mapping.", var))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut b = board.clone();
+    place_pieces(&mut b, shapes, &chosen)?;
+    if !b.remaining().is_empty() {
+        return Err("The model leaves cells uncovered.".to_string());
+    }
+
+    Ok(b)
+}
+
+// Sets literals forced by clauses with a single open literal, returning
+// false on a conflict
+fn propagate(cnf: &Cnf, values: &mut [Option<bool>], trail: &mut Vec<usize>) -> bool {
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+        for clause in &cnf.clauses {
+            let mut open = None;
+            let mut open_count = 0;
+            let mut satisfied = false;
+
+            for &lit in clause {
+                match values[lit.unsigned_abs() as usize] {
+                    Some(value) if value == (lit > 0) => {
+                        satisfied = true;
+                        break;
+                    }
+                    Some(_) => (),
+                    None => {
+                        open = Some(lit);
+                        open_count += 1;
+                    }
+                }
+            }
+
+            match (satisfied, open_count, open) {
+                (true, _, _) => (),
+                (false, 0, _) => return false,
+                (false, 1, Some(lit)) => {
+                    values[lit.unsigned_abs() as usize] = Some(lit > 0);
+                    trail.push(lit.unsigned_abs() as usize);
+                    changed = true;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    true
+}
+
+fn search(cnf: &Cnf, values: &mut Vec<Option<bool>>) -> bool {
+    let mut trail = vec![];
+    if !propagate(cnf, values, &mut trail) {
+        for var in trail {
+            values[var] = None;
+        }
+        return false;
+    }
+
+    // branch on a variable of the shortest open clause
+    let branch = cnf
+        .clauses
+        .iter()
+        .filter(|clause| {
+            !clause
+                .iter()
+                .any(|&lit| values[lit.unsigned_abs() as usize] == Some(lit > 0))
+        })
+        .map(|clause| {
+            clause
+                .iter()
+                .filter(|&&lit| values[lit.unsigned_abs() as usize].is_none())
+                .collect::<Vec<_>>()
+        })
+        .min_by_key(|open| open.len())
+        .map(|open| open[0].unsigned_abs() as usize);
+
+    let var = match branch {
+        Some(var) => var,
+        None => return true,
+    };
+
+    for value in [true, false] {
+        values[var] = Some(value);
+        if search(cnf, values) {
+            return true;
+        }
+    }
+    values[var] = None;
+
+    for var in trail {
+        values[var] = None;
+    }
+    false
+}
+
+// A plain DPLL solver: unit propagation and backtracking, good enough for
+// checking the exported formulas without an external tool. Returns the true
+// variables of a model
+pub fn dpll(cnf: &Cnf) -> Option<Vec<usize>> {
+    let mut values = vec![None; cnf.vars + 1];
+    if !search(cnf, &mut values) {
+        return None;
+    }
+
+    Some(
+        (1..=cnf.vars)
+            .filter(|&var| values[var] == Some(true))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdcl::{block, create_cdcl, solve};
+    use crate::memo::count_solutions;
+    use crate::puzzle::parse_puzzle;
+
+    // a rectangle five cells wide with the given pentominoes
+    fn pentominoes(rows: usize, labels: &str) -> crate::puzzle::Puzzle {
+        let mut text = "board 2\n".to_string() + &"o o o o o\n".repeat(rows);
+        for label in labels.chars() {
+            let drawing = match label {
+                'F' => " **\n**\n *",
+                'I' => "*****",
+                'L' => "****\n*",
+                'P' => "***\n**",
+                'T' => "***\n *\n *",
+                'U' => "* *\n***",
+                'V' => "*\n*\n***",
+                'Y' => "****\n *",
+                _ => unreachable!(),
+            };
+            text += &format!("piece {}\n{}\n", label, drawing);
+        }
+        parse_puzzle(&text).unwrap()
+    }
+
+    #[test]
+    fn model_found_by_dpll_covers_the_board() {
+        let puzzle = pentominoes(5, "ILPTY");
+        let board = puzzle.make_board(&[]).unwrap();
+        let (cnf, placements) = to_cnf(&puzzle.placement_table(), &board);
+
+        // the mapping file is read back the way import-model does
+        let (labels, placements) = parse_mapping(&write_mapping(&[], &placements)).unwrap();
+        assert!(labels.is_empty());
+
+        let trues = dpll(&cnf).unwrap();
+        assert_eq!(trues.len(), puzzle.shapes.len());
+        let solved = model_to_board(&board, &puzzle.shapes, &placements, &trues).unwrap();
+        assert!(solved.remaining().is_empty());
+    }
+
+    #[test]
+    fn dpll_finds_no_model_without_a_tiling() {
+        // the pieces cover a 3x5 rectangle but cannot tile it
+        let puzzle = pentominoes(3, "LPY");
+        let board = puzzle.make_board(&[]).unwrap();
+        let (cnf, _) = to_cnf(&puzzle.placement_table(), &board);
+        assert_eq!(dpll(&cnf), None);
+    }
+
+    #[test]
+    fn copies_are_counted_once_by_cdcl() {
+        // two L tetrominoes and two copies of I in a 4x4 square
+        let text = "board 2\n".to_string()
+            + &"o o o o\n".repeat(4)
+            + "piece L x2\n***\n*\npiece I x2\n****\n";
+        let puzzle = parse_puzzle(&text).unwrap();
+        let board = puzzle.make_board(&[]).unwrap();
+        let table = puzzle.placement_table();
+        let (cnf, _) = to_cnf(&table, &board);
+
+        let mut engine = create_cdcl(&cnf);
+        let mut count = 0;
+        while let Some(trues) = solve(&mut engine) {
+            block(&mut engine, &trues);
+            count += 1;
+        }
+        let (expected, _) = count_solutions(&table, &board, &puzzle.shapes, None).unwrap();
+        assert!(expected > 0);
+        assert_eq!(count, expected);
+    }
+
+    #[test]
+    fn models_and_mappings_are_parsed() {
+        let model = "c comment\ns SATISFIABLE\nv 1 -2 3\nv -4 5 0\n";
+        assert_eq!(parse_model(model), Ok(Some(vec![1, 3, 5])));
+        assert_eq!(parse_model("1 -2 0"), Ok(Some(vec![1])));
+        assert_eq!(parse_model("s UNSATISFIABLE\n"), Ok(None));
+        assert_eq!(parse_model("UNSAT"), Ok(None));
+        assert!(parse_model("v 1 x 0").is_err());
+
+        let (labels, placements) = parse_mapping("labels Jan 1\n1 Z:0@0,0\n2 V:3@2,1\n").unwrap();
+        assert_eq!(labels, ["Jan", "1"]);
+        assert_eq!(placements.len(), 2);
+        assert_eq!(placements[1].label, "V");
+        assert_eq!(placements[1].variant, 3);
+        assert_eq!(placements[1].offset, Point { x: 2, y: 1 });
+        assert!(parse_mapping("1 Z:0@0,0\n").is_err());
+        assert!(parse_mapping("labels\n2 Z:0@0,0\n").is_err());
+    }
+}
@@ -1,32 +1,53 @@
+use std::ops::Range;
+use std::sync::Arc;
+
 use crate::board::Board;
-use crate::geometry::variants;
-use crate::geometry::Point;
 use crate::geometry::Shape;
+use crate::rng::Rng;
+use crate::table::{create_placement_table, PlacementTable};
+use crate::topology::Topology;
 
 struct ShapeState<P: Clone> {
     point_index: usize,
     variant_index: usize,
-    remove: Option<Vec<Point>>,
+    remove: Option<Vec<P>>,
     places: usize,
     label: String,
+    // the piece in the placement table, and the table cell of each point
+    piece: usize,
     points: Vec<P>,
+    cells: Vec<usize>,
+    variant_order: Vec<usize>,
+    // where the first cell may go, with copies of the piece on the board
+    firsts: Range<usize>,
 }
 
-fn new_shape_state(label: String, ps: Vec<Point>) -> ShapeState<Point> {
+fn new_shape_state<P: Clone>(
+    label: String,
+    piece: usize,
+    ps: Vec<P>,
+    cells: Vec<usize>,
+    variant_order: Vec<usize>,
+    firsts: Range<usize>,
+) -> ShapeState<P> {
     ShapeState {
         point_index: 0,
         variant_index: 0,
         remove: None,
         places: 0,
         label: label.to_string(),
+        piece,
         points: ps,
+        cells,
+        variant_order,
+        firsts,
     }
 }
 
-fn step_state(
-    state: &mut ShapeState<Point>,
-    board: &mut Board<Point>,
-    base_variants: &[Vec<Point>],
+fn step_state<T: Topology>(
+    state: &mut ShapeState<T::Point>,
+    board: &mut Board<T>,
+    table: &PlacementTable<T>,
     min_size: usize,
 ) -> bool {
     if let Some(remove) = state.remove.take() {
@@ -34,19 +55,20 @@ fn step_state(
         state.remove = None;
     }
 
-    if state.variant_index < base_variants.len() {
-        state.remove = board.fill(
-            &base_variants[state.variant_index],
-            state.points[state.point_index],
-            &state.label,
-        );
+    let variant_count = table.variants[state.piece].len();
+    if state.variant_index < variant_count {
+        let variant = state.variant_order[state.variant_index];
+        state.remove = table
+            .anchored(state.piece, variant, state.cells[state.point_index])
+            .filter(|entry| state.firsts.contains(&entry.first))
+            .and_then(|entry| board.fill_points(&entry.points, &state.label));
 
         if state.remove.is_some() {
             state.places += 1;
         }
 
         state.variant_index += 1;
-    } else if state.variant_index == base_variants.len() {
+    } else if state.variant_index == variant_count {
         let p = state.points[state.point_index];
 
         if board.reachable(&p, min_size) < min_size {
@@ -68,44 +90,146 @@ fn never_placed<P: Clone>(state: &ShapeState<P>) -> bool {
     state.places == 0
 }
 
-pub struct Solver<P: Clone> {
-    board: Board<P>,
-    labeled_shapes: Vec<(String, Shape<P>)>,
-    shape_states: Vec<ShapeState<P>>,
-    base_variants: Vec<Vec<Vec<Point>>>,
+pub struct Solver<T: Topology> {
+    board: Board<T>,
+    labeled_shapes: Vec<(String, Shape<T::Point>)>,
+    shape_states: Vec<ShapeState<T::Point>>,
+    table: Arc<PlacementTable<T>>,
+    // table piece of each shape
+    pieces: Vec<usize>,
     min_size: usize,
+    rng: Option<Rng>,
+    // a solver given no pieces has the board as its one solution, reported
+    // by the first step
+    unreported: bool,
+}
+
+fn shuffle<I>(rng: &mut Rng, items: &mut [I]) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i + 1));
+    }
 }
 
-fn next_shape_state(solver: &Solver<Point>) -> ShapeState<Point> {
+fn next_shape_state<T: Topology>(solver: &mut Solver<T>) -> ShapeState<T::Point> {
     let i = solver.shape_states.len();
+    let piece = solver.pieces[i];
+    let mut variant_order: Vec<usize> = (0..solver.table.variants[piece].len()).collect();
+
+    let points = if let Some(rng) = &mut solver.rng {
+        // start from board order so that a seed always gives the same search
+        let mut points: Vec<T::Point> = solver
+            .board
+            .all
+            .iter()
+            .filter(|p| solver.board.at(p).is_none())
+            .cloned()
+            .collect();
+        shuffle(rng, &mut points);
+        shuffle(rng, &mut variant_order);
+        points
+    } else {
+        solver.board.remaining().iter().map(|p| *(*p)).collect()
+    };
+
+    let cells = points
+        .iter()
+        .map(|p| solver.table.cell(p).unwrap())
+        .collect();
+    let firsts = solver.table.copy_range(&solver.board, piece);
     new_shape_state(
         solver.labeled_shapes[i].0.clone(),
-        solver.board.remaining().iter().map(|p| *(*p)).collect(),
+        piece,
+        points,
+        cells,
+        variant_order,
+        firsts,
     )
 }
 
-pub fn create_solver(b: Board<Point>, shapes: Vec<(String, Shape<Point>)>) -> Solver<Point> {
+pub fn create_solver<T: Topology>(
+    b: Board<T>,
+    shapes: Vec<(String, Shape<T::Point>)>,
+) -> Solver<T> {
+    let table = create_placement_table(&b, &shapes);
+    create_solver_with_table(b, shapes, table)
+}
+
+// A solver looking its placements up in a table made for the whole board,
+// which may hold more pieces than it is given
+pub fn create_solver_with_table<T: Topology>(
+    b: Board<T>,
+    shapes: Vec<(String, Shape<T::Point>)>,
+    table: Arc<PlacementTable<T>>,
+) -> Solver<T> {
     let count = shapes.len();
     let min_size = shapes
         .iter()
         .map(|(_, shape)| shape.points.len())
         .min()
-        .unwrap();
-    let base_variants = shapes
+        .unwrap_or(0);
+    let pieces = shapes
         .iter()
-        .map(|(_, shape)| variants(shape))
-        .collect::<Vec<_>>();
+        .map(|(label, _)| {
+            table
+                .piece(label)
+                .expect("piece missing from the placement table")
+        })
+        .collect();
     let mut solver = Solver {
         board: b,
         labeled_shapes: shapes,
         shape_states: Vec::with_capacity(count),
-        base_variants,
+        table,
+        pieces,
         min_size,
+        rng: None,
+        unreported: count == 0,
     };
 
-    let state = next_shape_state(&solver);
-    solver.shape_states.push(state);
+    if count > 0 {
+        let state = next_shape_state(&mut solver);
+        solver.shape_states.push(state);
+    }
+    solver
+}
+
+// Makes the search try points and orientations in a random order, so each
+// seed leads to a different first solution
+pub fn randomize<T: Topology>(solver: &mut Solver<T>, rng: Rng) {
+    solver.rng = Some(rng);
+    solver.shape_states.clear();
+
+    if !solver.labeled_shapes.is_empty() {
+        let state = next_shape_state(solver);
+        solver.shape_states.push(state);
+    }
+}
+
+// A single piece placed on the board: which variant of it and where
+#[derive(Debug, Clone)]
+pub struct Placement<P> {
+    pub label: String,
+    pub variant: usize,
+    pub offset: P,
+}
+
+// Placements of the shapes currently on the board, in placement order
+pub fn placements<T: Topology>(solver: &Solver<T>) -> Vec<Placement<T::Point>> {
     solver
+        .shape_states
+        .iter()
+        .filter(|state| is_placed(state))
+        .map(|state| {
+            let variant = state.variant_order[state.variant_index - 1];
+            let cell = state.cells[state.point_index];
+            solver
+                .table
+                .anchored(state.piece, variant, cell)
+                .unwrap()
+                .placement
+                .clone()
+        })
+        .collect()
 }
 
 pub enum StepEvent {
@@ -114,23 +238,25 @@ pub enum StepEvent {
     Solved,
 }
 
-pub fn step<F>(solver: &mut Solver<Point>, mut callback: F) -> bool
+pub fn step<T, F>(solver: &mut Solver<T>, mut callback: F) -> bool
 where
-    F: FnMut(StepEvent, &Board<Point>),
+    T: Topology,
+    F: FnMut(StepEvent, &Board<T>),
 {
+    if solver.unreported {
+        solver.unreported = false;
+        if solver.board.remaining().is_empty() {
+            callback(StepEvent::Solved, &solver.board);
+        }
+        return false;
+    }
     if solver.shape_states.is_empty() {
         return false; // No shapes to place
     }
 
-    let i = solver.shape_states.len() - 1;
     let state = solver.shape_states.last_mut().unwrap();
 
-    let more = step_state(
-        state,
-        &mut solver.board,
-        &solver.base_variants[i],
-        solver.min_size,
-    );
+    let more = step_state(state, &mut solver.board, &solver.table, solver.min_size);
     if !more && is_placed(state) {
         callback(StepEvent::Placed, &solver.board);
     }
@@ -144,7 +270,9 @@ where
         !solver.shape_states.is_empty()
     } else {
         if is_placed(state) {
-            let solved = solver.shape_states.len() == solver.labeled_shapes.len();
+            // every piece is down, which only solves the board if they cover it
+            let all_placed = solver.shape_states.len() == solver.labeled_shapes.len();
+            let solved = all_placed && solver.board.remaining().is_empty();
 
             callback(
                 if solved {
@@ -155,7 +283,7 @@ where
                 &solver.board,
             );
 
-            if !solved {
+            if !all_placed {
                 let state = next_shape_state(solver);
                 solver.shape_states.push(state);
             }
@@ -163,3 +291,26 @@ where
         true
     }
 }
+
+// Runs the search until the first solution and returns how it was reached
+pub fn solve_first<T: Topology>(
+    b: Board<T>,
+    shapes: Vec<(String, Shape<T::Point>)>,
+) -> Option<Vec<Placement<T::Point>>> {
+    let mut solver = create_solver(b, shapes);
+    let mut solved = false;
+
+    while !solved
+        && step(&mut solver, |e, _| {
+            if let StepEvent::Solved = e {
+                solved = true;
+            }
+        })
+    {}
+
+    if solved {
+        Some(placements(&solver))
+    } else {
+        None
+    }
+}
@@ -1,4 +1,9 @@
-use crate::geometry::{LabeledPoint, LabeledPoints, Point, Shape, VisualShape};
+use crate::board::{make_point_board, Board};
+use crate::geometry::{
+    HexPoint, LabeledPoint, LabeledPoints, Point, Point3, Shape, TriPoint, VisualShape,
+};
+use crate::solver::Placement;
+use crate::topology::{Cube, Square, Topology, Tri, Wrapped};
 
 pub fn convert_to_points(shape: &[&str], blank: &str) -> Vec<Point> {
     let mut points = Vec::new();
@@ -71,6 +76,32 @@ pub fn convert_to_strings(ps: &[Point], to_char: impl Fn(&Point) -> char) -> Vec
     grid.iter().map(|cs| cs.iter().collect()).collect()
 }
 
+// Draws a wrapped board twice along each wrapped axis, with '|' and '='
+// marking the seam, so pieces running over an edge show up in one piece
+pub fn convert_wrapped_to_strings(
+    ps: &[Point],
+    topology: &Wrapped,
+    to_char: impl Fn(&Point) -> char,
+) -> Vec<String> {
+    let mut lines = convert_to_strings(ps, to_char);
+
+    if topology.width.is_some() {
+        lines = lines
+            .iter()
+            .map(|line| format!("{}|{}", line, line))
+            .collect();
+    }
+    if topology.height.is_some() {
+        let width = lines.first().map_or(0, |line| line.chars().count());
+        let mut tiled = lines.clone();
+        tiled.push("=".repeat(width));
+        tiled.extend(lines);
+        lines = tiled;
+    }
+
+    lines
+}
+
 // used to zero out shape coordinates so the first point is always (0, 0)
 // which is important for the solver's iteration
 fn subtract(p1: Point, p2: Point) -> Point {
@@ -89,3 +120,242 @@ pub fn convert_to_shape(vs: &VisualShape) -> Shape<Point> {
         points: points.iter().map(|p| subtract(*p, first)).collect(),
     }
 }
+
+// Placements are written as "label:orientation@x,y", e.g. "Z:2@3,4"
+pub fn parse_placement(s: &str) -> Option<Placement<Point>> {
+    let (label, rest) = s.split_once(':')?;
+    let (variant, offset) = rest.split_once('@')?;
+    let (x, y) = offset.split_once(',')?;
+
+    Some(Placement {
+        label: label.to_string(),
+        variant: variant.parse().ok()?,
+        offset: Point {
+            x: x.parse().ok()?,
+            y: y.parse().ok()?,
+        },
+    })
+}
+
+pub fn format_placement(pl: &Placement<Point>) -> String {
+    format!(
+        "{}:{}@{},{}",
+        pl.label, pl.variant, pl.offset.x, pl.offset.y
+    )
+}
+
+// Reads a grid as written by print_board back into a board over the given
+// points; '-' is an uncovered cell and '*' a blocked one
+pub fn parse_board(lines: &[&str], ps: &[Point]) -> Result<Board<Square>, String> {
+    let (min, _) = bounds(ps);
+    let mut board = make_point_board(ps.to_vec());
+
+    for p in ps {
+        let c = lines
+            .get((p.y - min.y) as usize)
+            .and_then(|line| line.chars().nth((p.x - min.x) as usize))
+            .unwrap_or(' ');
+
+        match c {
+            ' ' => return Err(format!("Missing cell at {},{}.", p.x, p.y)),
+            '-' => (),
+            _ => {
+                board.fill(&vec![*p], Point { x: 0, y: 0 }, &c.to_string());
+            }
+        }
+    }
+
+    Ok(board)
+}
+
+// Boards are printed with one character per cell, the first of the label
+pub fn marker(label: &str) -> String {
+    label
+        .chars()
+        .next()
+        .map(|c| c.to_string())
+        .unwrap_or_default()
+}
+
+// Hex cells are drawn on every other column with each row shifted by half a
+// cell; the first cell drawn decides which columns hold cells, characters in
+// between them are ignored
+pub fn convert_to_hex_points(shape: &[&str], blank: &str) -> Vec<HexPoint> {
+    let mut points = Vec::new();
+    let mut parity = None;
+
+    for (y, line) in shape.iter().enumerate() {
+        for (x, c) in line.chars().enumerate() {
+            if c.to_string() != blank {
+                let parity = *parity.get_or_insert((x + y) % 2);
+
+                if (x + y) % 2 == parity {
+                    points.push(HexPoint {
+                        q: (x as i16 - parity as i16 - y as i16) / 2,
+                        r: y as i16,
+                    });
+                }
+            }
+        }
+    }
+
+    points
+}
+
+pub fn convert_hex_to_strings(ps: &[HexPoint], to_char: impl Fn(&HexPoint) -> char) -> Vec<String> {
+    let columns: Vec<Point> = ps
+        .iter()
+        .map(|p| Point {
+            x: 2 * p.q + p.r,
+            y: p.r,
+        })
+        .collect();
+    let (min, max) = bounds(&columns);
+    let width = (max.x - min.x + 1) as usize;
+    let height = (max.y - min.y + 1) as usize;
+
+    let mut grid = vec![vec![' '; width]; height];
+
+    for (p, c) in ps.iter().zip(&columns) {
+        grid[(c.y - min.y) as usize][(c.x - min.x) as usize] = to_char(p);
+    }
+
+    grid.iter().map(|cs| cs.iter().collect()).collect()
+}
+
+pub fn convert_to_hex_shape(vs: &VisualShape) -> Shape<HexPoint> {
+    let string_slices: Vec<&str> = vs.points.iter().map(|s| s.as_str()).collect();
+    let points = convert_to_hex_points(&string_slices, " ");
+    let first = points[0];
+
+    Shape {
+        attrs: vs.attrs.clone(),
+        points: points
+            .iter()
+            .map(|p| HexPoint {
+                q: p.q - first.q,
+                r: p.r - first.r,
+            })
+            .collect(),
+    }
+}
+
+// Triangle cells are drawn one per column, alternating between pointing up
+// and down: the character at column x of row y points up when x + y is even,
+// and shares its lower edge with the one right below it
+pub fn convert_to_tri_points(shape: &[&str], blank: &str) -> Vec<TriPoint> {
+    let mut points = Vec::new();
+
+    for (y, line) in shape.iter().enumerate() {
+        for (x, c) in line.chars().enumerate() {
+            if c.to_string() != blank {
+                let up = (x + y) % 2 == 0;
+                let k = if up { 0 } else { 1 };
+                points.push(TriPoint::new(((x + y - k) / 2) as i16, -(y as i16), up));
+            }
+        }
+    }
+
+    points
+}
+
+pub fn convert_tri_to_strings(ps: &[TriPoint], to_char: impl Fn(&TriPoint) -> char) -> Vec<String> {
+    let columns: Vec<Point> = ps
+        .iter()
+        .map(|p| {
+            let (a, b) = p.cell();
+            let k = if p.up() { 0 } else { 1 };
+            Point {
+                x: 2 * a + b + k,
+                y: -b,
+            }
+        })
+        .collect();
+    let (mut min, max) = bounds(&columns);
+    // keep which columns point up when read back in
+    if (min.x + min.y).rem_euclid(2) != 0 {
+        min.x -= 1;
+    }
+    let width = (max.x - min.x + 1) as usize;
+    let height = (max.y - min.y + 1) as usize;
+
+    let mut grid = vec![vec![' '; width]; height];
+
+    for (p, c) in ps.iter().zip(&columns) {
+        grid[(c.y - min.y) as usize][(c.x - min.x) as usize] = to_char(p);
+    }
+
+    grid.iter().map(|cs| cs.iter().collect()).collect()
+}
+
+pub fn convert_to_tri_shape(vs: &VisualShape) -> Shape<TriPoint> {
+    let string_slices: Vec<&str> = vs.points.iter().map(|s| s.as_str()).collect();
+    let points = convert_to_tri_points(&string_slices, " ");
+
+    Shape {
+        attrs: vs.attrs.clone(),
+        points: Tri.normalize(&points),
+    }
+}
+
+// Solids are drawn one layer at a time from the bottom up, with the layers
+// separated by empty lines; rows that have no cubes need at least one blank
+// character so they do not end the layer
+pub fn convert_to_cube_points(shape: &[&str], blank: &str) -> Vec<Point3> {
+    let mut points = Vec::new();
+    let (mut y, mut z) = (0, 0);
+
+    for line in shape {
+        if line.trim().is_empty() {
+            if y > 0 {
+                z += 1;
+                y = 0;
+            }
+            continue;
+        }
+
+        for (x, c) in line.chars().enumerate() {
+            if c.to_string() != blank && !c.is_whitespace() {
+                points.push(Point3 {
+                    x: x as i16,
+                    y: y as i16,
+                    z,
+                });
+            }
+        }
+        y += 1;
+    }
+
+    points
+}
+
+// Prints the layers side by side, bottom layer first
+pub fn convert_cube_to_strings(ps: &[Point3], to_char: impl Fn(&Point3) -> char) -> Vec<String> {
+    let flat: Vec<Point> = ps.iter().map(|p| Point { x: p.x, y: p.y }).collect();
+    let (min, max) = bounds(&flat);
+    let min_z = ps.iter().map(|p| p.z).min().unwrap_or(0);
+    let max_z = ps.iter().map(|p| p.z).max().unwrap_or(0);
+
+    let width = (max.x - min.x + 1) as usize;
+    let height = (max.y - min.y + 1) as usize;
+    let layers = (max_z - min_z + 1) as usize;
+
+    let mut grid = vec![vec![' '; (width + 2) * layers - 2]; height];
+
+    for p in ps {
+        let column = (p.z - min_z) as usize * (width + 2) + (p.x - min.x) as usize;
+        grid[(p.y - min.y) as usize][column] = to_char(p);
+    }
+
+    grid.iter().map(|cs| cs.iter().collect()).collect()
+}
+
+pub fn convert_to_cube_shape(vs: &VisualShape) -> Shape<Point3> {
+    let string_slices: Vec<&str> = vs.points.iter().map(|s| s.as_str()).collect();
+    let points = convert_to_cube_points(&string_slices, ".");
+
+    Shape {
+        attrs: vs.attrs.clone(),
+        points: Cube.normalize(&points),
+    }
+}
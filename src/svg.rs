@@ -0,0 +1,106 @@
+use crate::geometry::{HexPoint, TriPoint};
+
+const SIZE: f64 = 20.0;
+
+// Picks a stable fill colour for a label; uncovered cells stay white and
+// blocked ones grey
+fn colour(label: Option<&str>) -> String {
+    match label {
+        None => "#ffffff".to_string(),
+        Some("*") => "#999999".to_string(),
+        Some(label) => {
+            let hue = label
+                .bytes()
+                .fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32))
+                % 360;
+            format!("hsl({}, 70%, 60%)", hue * 47 % 360)
+        }
+    }
+}
+
+fn polygon(corners: &[(f64, f64)], label: Option<&str>) -> String {
+    let points: Vec<String> = corners
+        .iter()
+        .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+        .collect();
+
+    format!(
+        "<polygon points=\"{}\" fill=\"{}\" stroke=\"#333333\" stroke-width=\"1\"/>",
+        points.join(" "),
+        colour(label)
+    )
+}
+
+type Corners = Vec<(f64, f64)>;
+
+fn document(shapes: Vec<(Corners, Option<&str>)>) -> String {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for (corners, _) in &shapes {
+        for (x, y) in corners {
+            min_x = min_x.min(*x);
+            min_y = min_y.min(*y);
+            max_x = max_x.max(*x);
+            max_y = max_y.max(*y);
+        }
+    }
+
+    let mut lines = vec![format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{:.1} {:.1} {:.1} {:.1}\">",
+        min_x - 2.0,
+        min_y - 2.0,
+        max_x - min_x + 4.0,
+        max_y - min_y + 4.0
+    )];
+    for (corners, label) in &shapes {
+        lines.push(format!("  {}", polygon(corners, *label)));
+    }
+    lines.push("</svg>".to_string());
+
+    lines.join("\n")
+}
+
+// Draws pointy topped hexagons, coloured by the label of each cell
+pub fn hex_to_svg<'a>(ps: &[HexPoint], label_at: impl Fn(&HexPoint) -> Option<&'a str>) -> String {
+    let shapes = ps
+        .iter()
+        .map(|p| {
+            let cx = SIZE * 3f64.sqrt() * (p.q as f64 + p.r as f64 / 2.0);
+            let cy = SIZE * 1.5 * p.r as f64;
+            let corners = (0..6)
+                .map(|i| {
+                    let angle = (60.0 * i as f64 - 30.0).to_radians();
+                    (cx + SIZE * angle.cos(), cy + SIZE * angle.sin())
+                })
+                .collect();
+            (corners, label_at(p))
+        })
+        .collect();
+
+    document(shapes)
+}
+
+// Draws each triangle from the lattice points around it, with the lattice
+// rows going up the page
+pub fn tri_to_svg<'a>(ps: &[TriPoint], label_at: impl Fn(&TriPoint) -> Option<&'a str>) -> String {
+    let vertex = |a: i16, b: i16| {
+        (
+            2.0 * SIZE * (a as f64 + b as f64 / 2.0),
+            -2.0 * SIZE * b as f64 * 3f64.sqrt() / 2.0,
+        )
+    };
+
+    let shapes = ps
+        .iter()
+        .map(|p| {
+            let (a, b) = p.cell();
+            let corners = if p.up() {
+                vec![vertex(a, b), vertex(a + 1, b), vertex(a, b + 1)]
+            } else {
+                vec![vertex(a + 1, b), vertex(a + 1, b + 1), vertex(a, b + 1)]
+            };
+            (corners, label_at(p))
+        })
+        .collect();
+
+    document(shapes)
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use crate::board::Board;
+use crate::geometry::canonical;
+use crate::table::PlacementTable;
+use crate::topology::Topology;
+
+// A rotation of the grid, after a reflection if `reflect` is set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub rotation: usize,
+    pub reflect: bool,
+}
+
+fn apply<T: Topology>(topology: &T, t: Transform, p: &T::Point) -> T::Point {
+    let p = if t.reflect { topology.reflect(p) } else { *p };
+    topology.rotate(&p, t.rotation)
+}
+
+// The transforms mapping the cells onto themselves up to translation, the
+// identity first. Only those taking every piece to one of its own
+// orientations are kept, as one-sided or solid pieces cannot be mirrored,
+// so the image of a solution under any of them is a solution too
+pub fn symmetries<T: Topology>(
+    topology: &T,
+    cells: &[T::Point],
+    table: &PlacementTable<T>,
+) -> Vec<Transform> {
+    let key = canonical(topology, cells);
+    let moves_pieces = |t: Transform| {
+        table.variants.iter().all(|vs| {
+            let image: Vec<T::Point> = vs[0].iter().map(|p| apply(topology, t, p)).collect();
+            let image = canonical(topology, &image);
+            vs.iter().any(|v| canonical(topology, v) == image)
+        })
+    };
+
+    let mut found = vec![];
+    for reflect in [false, true] {
+        for rotation in 0..topology.rotations() {
+            let t = Transform { rotation, reflect };
+            let image: Vec<T::Point> = cells.iter().map(|p| apply(topology, t, p)).collect();
+            if canonical(topology, &image) == key && moves_pieces(t) {
+                found.push(t);
+            }
+        }
+    }
+
+    found
+}
+
+// The solution seen through a transform, as cells with their markers in
+// point order, moved so the first cell is at the origin. Copies of a piece
+// are renamed in the order they turn up, as they can stand in for each other
+fn image<T: Topology>(
+    board: &Board<T>,
+    cells: &[T::Point],
+    t: Transform,
+    table: &PlacementTable<T>,
+) -> Vec<(T::Point, String)> {
+    let topology = board.topology();
+    let mut marked: Vec<(T::Point, String)> = cells
+        .iter()
+        .map(|p| {
+            let marker = board.at(p).unwrap_or("").to_string();
+            (apply(topology, t, p), marker)
+        })
+        .collect();
+    marked.sort();
+
+    let mut names: HashMap<String, String> = HashMap::new();
+    let mut used = vec![0; table.labels.len()];
+    for (_, marker) in marked.iter_mut() {
+        if let Some(piece) = table.piece(marker) {
+            let kind = table.kinds[piece];
+            let name = names.entry(marker.clone()).or_insert_with(|| {
+                let copy = (0..table.labels.len())
+                    .filter(|&p| table.kinds[p] == kind)
+                    .nth(used[kind])
+                    .unwrap();
+                used[kind] += 1;
+                table.labels[copy].clone()
+            });
+            *marker = name.clone();
+        }
+    }
+
+    let points: Vec<T::Point> = marked.iter().map(|(p, _)| *p).collect();
+    topology
+        .normalize(&points)
+        .into_iter()
+        .zip(marked.into_iter().map(|(_, marker)| marker))
+        .collect()
+}
+
+// Whether the solved board comes first among its images under the
+// symmetries of its open cells. Exactly one solution out of each set of
+// symmetric ones passes, so counting those counts solutions up to symmetry
+pub fn is_canonical<T: Topology>(
+    board: &Board<T>,
+    cells: &[T::Point],
+    symmetries: &[Transform],
+    table: &PlacementTable<T>,
+) -> bool {
+    let own = image(board, cells, symmetries[0], table);
+    symmetries[1..]
+        .iter()
+        .all(|&t| own <= image(board, cells, t, table))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::enumerate::{attrs, Symmetry};
+    use crate::puzzle::{parse_puzzle, Puzzle};
+    use crate::solver::{create_solver_with_table, step, StepEvent};
+    use crate::topology::Square;
+
+    // The LPTY pentominoes in a 4x5 rectangle
+    fn rectangle(symmetry: Symmetry) -> Puzzle {
+        let text = "board 2\n".to_string()
+            + &"o o o o o\n".repeat(4)
+            + "piece L\n****\n*\npiece P\n***\n**\npiece T\n***\n *\n *\npiece Y\n****\n *\n";
+        let mut puzzle = parse_puzzle(&text).unwrap();
+        for (_, shape) in puzzle.shapes.iter_mut() {
+            shape.attrs = attrs(&Square, &shape.points, symmetry);
+        }
+        puzzle
+    }
+
+    // The symmetries of the open cells, every solution and the canonical ones
+    fn count(puzzle: &Puzzle) -> (usize, usize, usize) {
+        let board = puzzle.make_board(&[]).unwrap();
+        let table = puzzle.placement_table();
+        let open: Vec<_> = board.remaining().into_iter().cloned().collect();
+        let symmetries = symmetries(&Square, &open, &table);
+
+        let mut solver = create_solver_with_table(board, puzzle.shapes.clone(), Arc::clone(&table));
+        let (mut found, mut unique) = (0, 0);
+        while step(&mut solver, |e, b| {
+            if let StepEvent::Solved = e {
+                found += 1;
+                if is_canonical(b, &open, &symmetries, &table) {
+                    unique += 1;
+                }
+            }
+        }) {}
+        (symmetries.len(), found, unique)
+    }
+
+    #[test]
+    fn free_pieces_are_unique_up_to_every_symmetry() {
+        assert_eq!(count(&rectangle(Symmetry::Free)), (4, 4, 1));
+    }
+
+    #[test]
+    fn one_sided_pieces_are_not_mirrored() {
+        // only the half turn is left, and the two solutions are half turns
+        // of each other
+        assert_eq!(count(&rectangle(Symmetry::OneSided)), (2, 2, 1));
+    }
+}
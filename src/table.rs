@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::board::Board;
+use crate::geometry::{kinds, variants, Shape};
+use crate::solver::Placement;
+use crate::topology::Topology;
+
+// One way a piece fits on the empty board, with the cells it covers
+pub struct Entry<P> {
+    pub piece: usize,
+    pub placement: Placement<P>,
+    pub points: Vec<P>,
+    // lowest table cell covered, which orders copies of a piece
+    pub first: usize,
+}
+
+// Every placement of every piece on the full board, worked out once per
+// puzzle. A date only has to leave out the entries over its blocked cells,
+// so one table behind an Arc serves all dates, engines and worker threads
+pub struct PlacementTable<T: Topology> {
+    pub cells: Vec<T::Point>,
+    pub labels: Vec<String>,
+    pub variants: Vec<Vec<Vec<T::Point>>>,
+    // the kind of each piece, shared by its copies
+    pub kinds: Vec<usize>,
+    // in piece, orientation and board order
+    pub entries: Vec<Entry<T::Point>>,
+    // entries covering each cell
+    pub by_cell: Vec<Vec<usize>>,
+    index: HashMap<T::Point, usize>,
+    // entry of each piece per cell and orientation, if it fits there
+    anchored: Vec<Vec<Option<usize>>>,
+}
+
+pub fn create_placement_table<T: Topology>(
+    board: &Board<T>,
+    shapes: &[(String, Shape<T::Point>)],
+) -> Arc<PlacementTable<T>> {
+    let mut empty = Board::new(board.all.clone(), board.topology().clone());
+    let index: HashMap<T::Point, usize> =
+        board.all.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+    let mut table = PlacementTable {
+        cells: board.all.clone(),
+        labels: shapes.iter().map(|(label, _)| label.clone()).collect(),
+        variants: vec![],
+        kinds: kinds(board.topology(), shapes),
+        entries: vec![],
+        by_cell: vec![vec![]; board.all.len()],
+        index,
+        anchored: vec![],
+    };
+
+    for (piece, (label, shape)) in shapes.iter().enumerate() {
+        let vs = variants(board.topology(), shape);
+        let mut anchored = vec![None; vs.len() * board.all.len()];
+
+        for (variant, v) in vs.iter().enumerate() {
+            for (cell, p) in board.all.iter().enumerate() {
+                // the offset that puts the variant's first point on the cell
+                let fits = board
+                    .topology()
+                    .offset_for(&v[0], p)
+                    .and_then(|offset| empty.fill(v, offset, label).map(|ps| (offset, ps)));
+                if let Some((offset, ps)) = fits {
+                    let id = table.entries.len();
+                    for p in &ps {
+                        table.by_cell[table.index[p]].push(id);
+                    }
+                    anchored[cell * vs.len() + variant] = Some(id);
+
+                    table.entries.push(Entry {
+                        piece,
+                        placement: Placement {
+                            label: label.clone(),
+                            variant,
+                            offset,
+                        },
+                        points: ps.clone(),
+                        first: ps.iter().map(|p| table.index[p]).min().unwrap(),
+                    });
+                    empty.unfill(ps);
+                }
+            }
+        }
+
+        table.variants.push(vs);
+        table.anchored.push(anchored);
+    }
+
+    Arc::new(table)
+}
+
+impl<T: Topology> PlacementTable<T> {
+    pub fn cell(&self, p: &T::Point) -> Option<usize> {
+        self.index.get(p).cloned()
+    }
+
+    pub fn piece(&self, label: &str) -> Option<usize> {
+        self.labels.iter().position(|l| l == label)
+    }
+
+    // The entry for a piece in the given orientation with its anchor on a cell
+    pub fn anchored(&self, piece: usize, variant: usize, cell: usize) -> Option<&Entry<T::Point>> {
+        let count = self.variants[piece].len();
+        self.anchored[piece][cell * count + variant].map(|id| &self.entries[id])
+    }
+
+    // Table cells the piece's first cell may take given the copies already
+    // on the board. Copies cover their first cells in the order they are
+    // listed, so swapping two of them never gives a new solution
+    pub fn copy_range(&self, board: &Board<T>, piece: usize) -> Range<usize> {
+        let mut range = 0..self.cells.len();
+        for (other, label) in self.labels.iter().enumerate() {
+            if other == piece || self.kinds[other] != self.kinds[piece] {
+                continue;
+            }
+            let placed = self
+                .cells
+                .iter()
+                .position(|p| board.at(p) == Some(label.as_str()));
+            match placed {
+                Some(cell) if other < piece => range.start = range.start.max(cell + 1),
+                Some(cell) => range.end = range.end.min(cell),
+                None => (),
+            }
+        }
+        range
+    }
+
+    // The next copy of a piece in list order, if it has one
+    pub fn next_copy(&self, piece: usize) -> Option<usize> {
+        (piece + 1..self.labels.len()).find(|&other| self.kinds[other] == self.kinds[piece])
+    }
+
+    // Pairs of open entries, as positions in `open`, that would put two copies
+    // out of order: the later copy's first cell at or before the earlier
+    // one's. Exporters rule these pairs out so that, as with `copy_range`,
+    // swapping copies never gives a new solution
+    pub fn copy_conflicts(&self, open: &[usize]) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for (i, &a) in open.iter().enumerate() {
+            let next = match self.next_copy(self.entries[a].piece) {
+                Some(next) => next,
+                None => continue,
+            };
+            for (j, &b) in open.iter().enumerate() {
+                let later = &self.entries[b];
+                if later.piece == next && later.first <= self.entries[a].first {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    // Whether none of the entry's cells are taken on the board
+    pub fn is_open(&self, board: &Board<T>, id: usize) -> bool {
+        self.entries[id]
+            .points
+            .iter()
+            .all(|p| board.at(p).is_none())
+    }
+
+    // Entries that fit on the open cells of the board, in table order, and
+    // where each entry ended up in that list
+    pub fn open_entries(&self, board: &Board<T>) -> (Vec<usize>, Vec<Option<usize>>) {
+        let mut open = vec![];
+        let mut position = vec![None; self.entries.len()];
+        for (id, slot) in position.iter_mut().enumerate() {
+            if self.is_open(board, id) {
+                *slot = Some(open.len());
+                open.push(id);
+            }
+        }
+        (open, position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::geometry::{HexPoint, TriPoint};
+    use crate::memo::count_solutions;
+    use crate::puzzle::{polyhex, polyiamond};
+    use crate::solver::solve_first;
+    use crate::topology::{Hex, Tri};
+
+    // Every placement of every piece found by trying each of the translations,
+    // as the piece and its sorted cells
+    fn brute_force<T: Topology>(
+        topology: &T,
+        cells: &[T::Point],
+        shapes: &[(String, Shape<T::Point>)],
+        translations: &[T::Point],
+    ) -> Vec<(usize, Vec<T::Point>)> {
+        let on_board: HashSet<T::Point> = cells.iter().cloned().collect();
+        let mut found = HashSet::new();
+        for (piece, (_, shape)) in shapes.iter().enumerate() {
+            for v in variants(topology, shape) {
+                for t in translations {
+                    let mut ps: Vec<T::Point> = v.iter().map(|p| topology.add(p, t)).collect();
+                    if ps.iter().all(|p| on_board.contains(p)) {
+                        ps.sort();
+                        found.insert((piece, ps));
+                    }
+                }
+            }
+        }
+        found.into_iter().collect()
+    }
+
+    // Tilings by exact cover over the placements, each piece used once
+    fn tilings<P: Copy + Eq + std::hash::Hash>(
+        free: &mut HashSet<P>,
+        cells: &[P],
+        used: &mut Vec<bool>,
+        placements: &[(usize, Vec<P>)],
+    ) -> usize {
+        let cell = match cells.iter().find(|p| free.contains(p)) {
+            Some(cell) => cell,
+            None => return used.iter().all(|u| *u) as usize,
+        };
+
+        let mut count = 0;
+        for (piece, ps) in placements {
+            if !used[*piece] && ps.contains(cell) && ps.iter().all(|p| free.contains(p)) {
+                used[*piece] = true;
+                ps.iter().for_each(|p| {
+                    free.remove(p);
+                });
+                count += tilings(free, cells, used, placements);
+                free.extend(ps.iter().cloned());
+                used[*piece] = false;
+            }
+        }
+        count
+    }
+
+    fn lattice(range: std::ops::Range<i16>) -> Vec<TriPoint> {
+        range
+            .clone()
+            .flat_map(|a| range.clone().map(move |b| TriPoint::new(a, b, true)))
+            .collect()
+    }
+
+    #[test]
+    fn triangle_table_has_every_placement() {
+        let puzzle = polyiamond();
+        let board = Board::new(puzzle.points(), Tri);
+        let table = create_placement_table(&board, &puzzle.shapes);
+
+        let placements = brute_force(&Tri, &board.all, &puzzle.shapes, &lattice(-16..16));
+        assert_eq!(table.entries.len(), placements.len());
+        assert_eq!(table.entries.len(), 399);
+
+        let mut free: HashSet<TriPoint> = board.all.iter().cloned().collect();
+        let mut used = vec![false; puzzle.shapes.len()];
+        let expected = tilings(&mut free, &board.all, &mut used, &placements);
+        let (count, _) = count_solutions(&table, &board, &puzzle.shapes, None).unwrap();
+        assert_eq!(count as usize, expected);
+        assert_eq!(count, 102);
+    }
+
+    #[test]
+    fn hex_table_has_every_placement_and_sample_solves() {
+        let puzzle = polyhex();
+        let board = Board::new(puzzle.points(), Hex);
+        let table = create_placement_table(&board, &puzzle.shapes);
+
+        let translations: Vec<HexPoint> = (-16..16)
+            .flat_map(|q| (-16..16).map(move |r| HexPoint { q, r }))
+            .collect();
+        let placements = brute_force(&Hex, &board.all, &puzzle.shapes, &translations);
+        assert_eq!(table.entries.len(), placements.len());
+        assert_eq!(table.entries.len(), 498);
+
+        let solution = solve_first(board.clone(), puzzle.shapes.clone()).unwrap();
+        assert_eq!(solution.len(), puzzle.shapes.len());
+        let mut solved = board.clone();
+        for pl in &solution {
+            let piece = table.piece(&pl.label).unwrap();
+            let v = &table.variants[piece][pl.variant];
+            assert!(solved.fill(v, pl.offset, &pl.label).is_some());
+        }
+        assert!(solved.remaining().is_empty());
+    }
+}
@@ -0,0 +1,358 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::geometry::{HexPoint, Point, Point3, TriPoint};
+
+// Describes the grid a board is made of: how points are translated, which
+// points are adjacent, and the symmetries pieces can be moved by
+pub trait Topology: Clone {
+    type Point: Copy + Eq + Ord + Hash + Debug;
+
+    fn add(&self, a: &Self::Point, b: &Self::Point) -> Self::Point;
+
+    fn neighbours(&self, p: &Self::Point) -> Vec<Self::Point>;
+
+    // Number of elements in the rotation group, rotation 0 being the identity
+    fn rotations(&self) -> usize;
+
+    fn rotate(&self, p: &Self::Point, rotation: usize) -> Self::Point;
+
+    fn reflect(&self, p: &Self::Point) -> Self::Point;
+
+    // Translates the points so the first one becomes the origin
+    fn normalize(&self, ps: &[Self::Point]) -> Vec<Self::Point>;
+
+    // The offset that moves the first point of a normalized shape onto the
+    // cell, if one does. Most grids normalize to the origin, so it is the
+    // cell itself
+    fn offset_for(&self, _first: &Self::Point, cell: &Self::Point) -> Option<Self::Point> {
+        Some(*cell)
+    }
+}
+
+// The plain square grid with four neighbours per cell
+#[derive(Clone, Copy, Debug)]
+pub struct Square;
+
+type Mapper = fn(Point) -> Point;
+
+const ROTATES: [Mapper; 4] = [
+    |p: Point| p,                          // 0 degrees rotation
+    |p: Point| Point { x: -p.y, y: p.x },  // 90 degrees rotation
+    |p: Point| Point { x: -p.x, y: -p.y }, // 180 degrees rotation
+    |p: Point| Point { x: p.y, y: -p.x },  // 270 degrees rotation
+];
+
+const DIRS: [Point; 4] = [
+    Point { x: 0, y: -1 },
+    Point { x: 0, y: 1 },
+    Point { x: -1, y: 0 },
+    Point { x: 1, y: 0 },
+];
+
+impl Topology for Square {
+    type Point = Point;
+
+    fn add(&self, a: &Point, b: &Point) -> Point {
+        Point {
+            x: a.x + b.x,
+            y: a.y + b.y,
+        }
+    }
+
+    fn neighbours(&self, p: &Point) -> Vec<Point> {
+        DIRS.iter().map(|d| self.add(p, d)).collect()
+    }
+
+    fn rotations(&self) -> usize {
+        ROTATES.len()
+    }
+
+    fn rotate(&self, p: &Point, rotation: usize) -> Point {
+        ROTATES[rotation](*p)
+    }
+
+    fn reflect(&self, p: &Point) -> Point {
+        Point { x: -p.x, y: p.y }
+    }
+
+    fn normalize(&self, ps: &[Point]) -> Vec<Point> {
+        match ps.first() {
+            Some(first) => ps
+                .iter()
+                .map(|p| Point {
+                    x: p.x - first.x,
+                    y: p.y - first.y,
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+// The square grid with opposite edges joined. Coordinates along a wrapped
+// axis are taken modulo its size, so wrapping one axis gives a cylinder and
+// both a torus; the board is expected to start at x = 0 and y = 0
+#[derive(Clone, Copy, Debug)]
+pub struct Wrapped {
+    pub width: Option<i16>,
+    pub height: Option<i16>,
+}
+
+impl Wrapped {
+    fn wrap(&self, p: Point) -> Point {
+        Point {
+            x: self.width.map_or(p.x, |w| p.x.rem_euclid(w)),
+            y: self.height.map_or(p.y, |h| p.y.rem_euclid(h)),
+        }
+    }
+}
+
+impl Topology for Wrapped {
+    type Point = Point;
+
+    fn add(&self, a: &Point, b: &Point) -> Point {
+        self.wrap(Square.add(a, b))
+    }
+
+    fn neighbours(&self, p: &Point) -> Vec<Point> {
+        DIRS.iter().map(|d| self.add(p, d)).collect()
+    }
+
+    fn rotations(&self) -> usize {
+        Square.rotations()
+    }
+
+    fn rotate(&self, p: &Point, rotation: usize) -> Point {
+        Square.rotate(p, rotation)
+    }
+
+    fn reflect(&self, p: &Point) -> Point {
+        Square.reflect(p)
+    }
+
+    // shapes themselves are never wrapped, only their placements
+    fn normalize(&self, ps: &[Point]) -> Vec<Point> {
+        Square.normalize(ps)
+    }
+}
+
+// Hexagonal cells in axial coordinates, with six neighbours and six rotations
+#[derive(Clone, Copy, Debug)]
+pub struct Hex;
+
+const HEX_DIRS: [HexPoint; 6] = [
+    HexPoint { q: 1, r: 0 },
+    HexPoint { q: -1, r: 0 },
+    HexPoint { q: 0, r: 1 },
+    HexPoint { q: 0, r: -1 },
+    HexPoint { q: 1, r: -1 },
+    HexPoint { q: -1, r: 1 },
+];
+
+impl Topology for Hex {
+    type Point = HexPoint;
+
+    fn add(&self, a: &HexPoint, b: &HexPoint) -> HexPoint {
+        HexPoint {
+            q: a.q + b.q,
+            r: a.r + b.r,
+        }
+    }
+
+    fn neighbours(&self, p: &HexPoint) -> Vec<HexPoint> {
+        HEX_DIRS.iter().map(|d| self.add(p, d)).collect()
+    }
+
+    fn rotations(&self) -> usize {
+        6
+    }
+
+    // each step turns the point 60 degrees around the origin
+    fn rotate(&self, p: &HexPoint, rotation: usize) -> HexPoint {
+        (0..rotation).fold(*p, |p, _| HexPoint {
+            q: -p.r,
+            r: p.q + p.r,
+        })
+    }
+
+    fn reflect(&self, p: &HexPoint) -> HexPoint {
+        HexPoint { q: p.r, r: p.q }
+    }
+
+    fn normalize(&self, ps: &[HexPoint]) -> Vec<HexPoint> {
+        match ps.first() {
+            Some(first) => ps
+                .iter()
+                .map(|p| HexPoint {
+                    q: p.q - first.q,
+                    r: p.r - first.r,
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+// Triangular cells. Adding a point to a cell of the other orientation gives
+// coordinates that are not a triangle at all, so a piece can only be placed
+// with its first triangle on a cell pointing the same way
+#[derive(Clone, Copy, Debug)]
+pub struct Tri;
+
+const UP_DIRS: [TriPoint; 3] = [
+    TriPoint { x: 1, y: 1 },
+    TriPoint { x: -2, y: 1 },
+    TriPoint { x: 1, y: -2 },
+];
+
+const DOWN_DIRS: [TriPoint; 3] = [
+    TriPoint { x: -1, y: -1 },
+    TriPoint { x: 2, y: -1 },
+    TriPoint { x: -1, y: 2 },
+];
+
+impl Topology for Tri {
+    type Point = TriPoint;
+
+    fn add(&self, a: &TriPoint, b: &TriPoint) -> TriPoint {
+        TriPoint {
+            x: a.x + b.x,
+            y: a.y + b.y,
+        }
+    }
+
+    fn neighbours(&self, p: &TriPoint) -> Vec<TriPoint> {
+        let dirs = if p.up() { &UP_DIRS } else { &DOWN_DIRS };
+        dirs.iter().map(|d| self.add(p, d)).collect()
+    }
+
+    fn rotations(&self) -> usize {
+        6
+    }
+
+    // each step turns the point 60 degrees around the lattice point at the
+    // origin, i.e. (-1, -1) in these coordinates
+    fn rotate(&self, p: &TriPoint, rotation: usize) -> TriPoint {
+        (0..rotation).fold(*p, |p, _| TriPoint {
+            x: -p.y - 2,
+            y: p.x + p.y + 1,
+        })
+    }
+
+    fn reflect(&self, p: &TriPoint) -> TriPoint {
+        TriPoint { x: p.y, y: p.x }
+    }
+
+    // Moves the first triangle into lattice cell (0, 0), keeping its direction
+    fn normalize(&self, ps: &[TriPoint]) -> Vec<TriPoint> {
+        match ps.first() {
+            Some(first) => {
+                let (a, b) = first.cell();
+                ps.iter()
+                    .map(|p| TriPoint {
+                        x: p.x - 3 * a,
+                        y: p.y - 3 * b,
+                    })
+                    .collect()
+            }
+            None => vec![],
+        }
+    }
+
+    // A downward first triangle sits at (1, 1), so the offset is a whole
+    // lattice cell and only exists for a cell pointing the same way
+    fn offset_for(&self, first: &TriPoint, cell: &TriPoint) -> Option<TriPoint> {
+        (first.up() == cell.up()).then(|| TriPoint {
+            x: cell.x - first.x,
+            y: cell.y - first.y,
+        })
+    }
+}
+
+// Cubic cells with six face neighbours. The rotation group holds the 24
+// proper rotations; reflections are only used for pieces marked chiral, as
+// solid pieces usually cannot be mirrored
+#[derive(Clone, Copy, Debug)]
+pub struct Cube;
+
+// Axis permutations, even ones first, and sign patterns with an even number
+// of flips; an even permutation with even flips or an odd one with odd flips
+// is a proper rotation
+const PERMUTATIONS: [[usize; 3]; 6] = [
+    [0, 1, 2],
+    [1, 2, 0],
+    [2, 0, 1],
+    [1, 0, 2],
+    [0, 2, 1],
+    [2, 1, 0],
+];
+
+const SIGNS: [[i16; 3]; 4] = [[1, 1, 1], [1, -1, -1], [-1, 1, -1], [-1, -1, 1]];
+
+const CUBE_DIRS: [Point3; 6] = [
+    Point3 { x: 1, y: 0, z: 0 },
+    Point3 { x: -1, y: 0, z: 0 },
+    Point3 { x: 0, y: 1, z: 0 },
+    Point3 { x: 0, y: -1, z: 0 },
+    Point3 { x: 0, y: 0, z: 1 },
+    Point3 { x: 0, y: 0, z: -1 },
+];
+
+impl Topology for Cube {
+    type Point = Point3;
+
+    fn add(&self, a: &Point3, b: &Point3) -> Point3 {
+        Point3 {
+            x: a.x + b.x,
+            y: a.y + b.y,
+            z: a.z + b.z,
+        }
+    }
+
+    fn neighbours(&self, p: &Point3) -> Vec<Point3> {
+        CUBE_DIRS.iter().map(|d| self.add(p, d)).collect()
+    }
+
+    fn rotations(&self) -> usize {
+        PERMUTATIONS.len() * SIGNS.len()
+    }
+
+    fn rotate(&self, p: &Point3, rotation: usize) -> Point3 {
+        let perm = PERMUTATIONS[rotation / SIGNS.len()];
+        let mut signs = SIGNS[rotation % SIGNS.len()];
+        if rotation / SIGNS.len() >= 3 {
+            // odd permutations need an odd number of flips
+            signs = [-signs[0], -signs[1], -signs[2]];
+        }
+
+        let c = [p.x, p.y, p.z];
+        Point3 {
+            x: signs[0] * c[perm[0]],
+            y: signs[1] * c[perm[1]],
+            z: signs[2] * c[perm[2]],
+        }
+    }
+
+    fn reflect(&self, p: &Point3) -> Point3 {
+        Point3 {
+            x: -p.x,
+            y: p.y,
+            z: p.z,
+        }
+    }
+
+    fn normalize(&self, ps: &[Point3]) -> Vec<Point3> {
+        match ps.first() {
+            Some(first) => ps
+                .iter()
+                .map(|p| Point3 {
+                    x: p.x - first.x,
+                    y: p.y - first.y,
+                    z: p.z - first.z,
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use crate::board::Board;
+use crate::geometry::{find_variant, variants, Point, Shape};
+use crate::puzzle::Puzzle;
+use crate::stringify::marker;
+use crate::topology::Square;
+
+// Cells that are not covered by any piece, in board order
+pub fn uncovered(board: &Board<Square>) -> Vec<Point> {
+    board
+        .all
+        .iter()
+        .filter(|p| matches!(board.at(p), None | Some("*")))
+        .cloned()
+        .collect()
+}
+
+// Checks a filled in board against the pieces and the cells that should be
+// left open, returning a description of every problem found
+pub fn verify(
+    board: &Board<Square>,
+    shapes: &[(String, Shape<Point>)],
+    blocked: &[Point],
+) -> Vec<String> {
+    let mut problems = vec![];
+    let mut pieces: HashMap<&str, Vec<Point>> = HashMap::new();
+
+    for p in &board.all {
+        if let Some(m) = board.at(p) {
+            if m != "*" {
+                pieces.entry(m).or_default().push(*p);
+            }
+        }
+    }
+
+    for (label, shape) in shapes {
+        match pieces.get(marker(label).as_str()) {
+            None => problems.push(format!("Piece {} is not used.", label)),
+            Some(ps) if ps.len() != shape.points.len() => problems.push(format!(
+                "Piece {} covers {} cells, expected {}.",
+                label,
+                ps.len(),
+                shape.points.len()
+            )),
+            Some(ps) => {
+                if find_variant(&Square, &variants(&Square, shape), ps).is_none() {
+                    problems.push(format!(
+                        "Piece {} does not match any of its orientations.",
+                        label
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut unknown: Vec<&&str> = pieces
+        .keys()
+        .filter(|m| !shapes.iter().any(|(label, _)| marker(label) == **m))
+        .collect();
+    unknown.sort();
+    for m in unknown {
+        problems.push(format!("Unknown piece {}.", m));
+    }
+
+    let open = uncovered(board);
+    for p in &open {
+        if !blocked.contains(p) {
+            problems.push(format!("Cell {},{} is left uncovered.", p.x, p.y));
+        }
+    }
+    for p in blocked {
+        if !open.contains(p) {
+            problems.push(format!("Cell {},{} should be left uncovered.", p.x, p.y));
+        }
+    }
+
+    problems
+}
+
+// Checks that cells left uncovered make a date: exactly one out of each
+// group and none outside the groups
+pub fn verify_date(puzzle: &Puzzle, open: &[Point]) -> Vec<String> {
+    let label = |p: &Point| {
+        puzzle
+            .cells
+            .iter()
+            .find(|lp| lp.point == *p)
+            .map(|lp| lp.label.as_str())
+    };
+
+    let mut problems = vec![];
+    for group in &puzzle.groups {
+        let count = open
+            .iter()
+            .filter(|p| group.iter().any(|l| Some(l.as_str()) == label(p)))
+            .count();
+        if count != 1 {
+            problems.push(format!(
+                "{} cells of {} are left uncovered, expected one.",
+                count,
+                group.join(" ")
+            ));
+        }
+    }
+    for p in open {
+        if !puzzle
+            .groups
+            .iter()
+            .flatten()
+            .any(|l| Some(l.as_str()) == label(p))
+        {
+            problems.push(format!("Cell {},{} is left uncovered.", p.x, p.y));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::puzzle::parse_puzzle;
+
+    // A 4x2 board with two groups, covered by single cell pieces everywhere
+    // but the given labels
+    fn covered_but(labels: &[&str]) -> (Puzzle, Board<Square>) {
+        let text = "board 2\nA B o o\nC D o o\ngroup A B\ngroup C D\npiece M x6\n*\n";
+        let puzzle = parse_puzzle(text).unwrap();
+        let labels: Vec<String> = labels.iter().map(|l| l.to_string()).collect();
+        let mut board = puzzle.make_board(&labels).unwrap();
+        let open: Vec<Point> = board.remaining().into_iter().cloned().collect();
+        for (p, (label, _)) in open.iter().zip(&puzzle.shapes) {
+            board.fill_points(&[*p], label);
+        }
+        (puzzle, board)
+    }
+
+    #[test]
+    fn uncovered_cells_must_make_a_date() {
+        let (puzzle, board) = covered_but(&["A", "D"]);
+        let open = uncovered(&board);
+        assert!(verify(&board, &puzzle.shapes, &open).is_empty());
+        assert!(verify_date(&puzzle, &open).is_empty());
+
+        // the pieces fit, but both cells of one group are left open
+        let (puzzle, board) = covered_but(&["A", "B"]);
+        let open = uncovered(&board);
+        assert!(verify(&board, &puzzle.shapes, &open).is_empty());
+        assert_eq!(
+            verify_date(&puzzle, &open),
+            [
+                "2 cells of A B are left uncovered, expected one.",
+                "0 cells of C D are left uncovered, expected one."
+            ]
+        );
+    }
+}